@@ -0,0 +1,71 @@
+// Time to find one RSA prime for 2048, 3072 and 4096-bit moduli, drawing fresh
+// candidates (FIPS 186-5 A.1.3) against the incremental sieve search, then the
+// parallel search for 4096-bit moduli on one thread against all cores.
+// Run with `cargo bench --bench prime_search`.
+use std::thread;
+use std::time::{Duration, Instant};
+
+use my_rsa::primes;
+use num_bigint::BigUint;
+use rand::SeedableRng;
+use rand_chacha::ChaCha20Rng;
+
+const PRIMES_PER_SIZE: u32 = 8;
+
+fn time<F: FnMut(&mut ChaCha20Rng) -> Option<BigUint>>(mut search: F) -> Duration {
+    let mut rng = ChaCha20Rng::seed_from_u64(0x5eed);
+    let start = Instant::now();
+    let mut found = 0;
+    while found < PRIMES_PER_SIZE {
+        if search(&mut rng).is_some() {
+            found += 1;
+        }
+    }
+    start.elapsed() / PRIMES_PER_SIZE
+}
+
+fn main() {
+    let e = BigUint::from(65537_u32);
+    println!(
+        "{:>6} {:>12} {:>12} {:>8}",
+        "nlen", "fips", "sieve", "speedup"
+    );
+    for nlen in [2048, 3072, 4096] {
+        let bits = nlen / 2;
+        let rounds = primes::miller_rabin_rounds(nlen);
+        let fips = time(|rng| primes::probable_prime(rng, bits, &e, rounds, None));
+        let sieve = time(|rng| primes::sieve_prime(rng, bits, &e, rounds, None));
+        println!(
+            "{:>6} {:>12.2?} {:>12.2?} {:>7.1}x",
+            nlen,
+            fips,
+            sieve,
+            fips.as_secs_f64() / sieve.as_secs_f64()
+        );
+    }
+
+    let cores = thread::available_parallelism().map_or(1, |n| n.get());
+    let rounds = primes::miller_rabin_rounds(4096);
+    println!();
+    println!(
+        "{:>6} {:>12} {:>12} {:>8}",
+        "search", "1 thread", "all cores", "speedup"
+    );
+    for search in [primes::PrimeSearch::Fips, primes::PrimeSearch::Sieve] {
+        let parallel = |threads| {
+            time(|_| {
+                Some(primes::parallel_prime(
+                    search, threads, 2048, &e, rounds, None,
+                ))
+            })
+        };
+        let (single, all) = (parallel(1), parallel(cores));
+        println!(
+            "{:>6} {:>12.2?} {:>12.2?} {:>7.1}x",
+            format!("{:?}", search).to_lowercase(),
+            single,
+            all,
+            single.as_secs_f64() / all.as_secs_f64()
+        );
+    }
+}
@@ -1,6 +1,6 @@
 use num::{Integer, One, ToPrimitive, Zero};
-use num_bigint::{BigInt, BigUint, RandBigInt};
-use rand;
+use num_bigint::{BigInt, BigUint, RandBigInt, Sign};
+use rand::RngCore;
 
 // Extended Euclidean Algorithm
 // Returns Bezout's identity coefficients and gcd(a,b)
@@ -33,75 +33,336 @@ fn test_ext_euc() {
     assert!(d == BigInt::from(2), "d = {}", d);
 }
 
-pub fn is_prime(proposal: &BigUint) -> bool {
+// Modular inverse of a modulo m, if gcd(a, m) = 1
+pub fn mod_inverse(a: &BigUint, m: &BigUint) -> Option<BigUint> {
+    let m_int = BigInt::from_biguint(Sign::Plus, m.clone());
+    let (s, _, d) = ext_euc(&BigInt::from_biguint(Sign::Plus, a.clone()), &m_int);
+    if !d.is_one() {
+        return None;
+    }
+    s.mod_floor(&m_int).to_biguint()
+}
+
+#[cfg(test)]
+#[test]
+fn test_mod_inverse() {
+    let m = BigUint::from(240_u32);
+    let a = BigUint::from(47_u32);
+    let inv = mod_inverse(&a, &m).unwrap();
+    assert!((a * &inv % &m).is_one(), "inv = {}", inv);
+    assert_eq!(mod_inverse(&BigUint::from(46_u32), &m), None);
+}
+
+// Recovers the prime factors of n from a key pair (n, e, d)
+// with the probabilistic method of NIST SP 800-56B, Appendix C
+pub fn recover_primes(n: &BigUint, e: &BigUint, d: &BigUint) -> Option<(BigUint, BigUint)> {
     let one: BigUint = BigUint::one();
-    let two: BigUint = &one + &one;
+    let n_minus_one: BigUint = n - &one;
+
+    // e * d - 1 = 2^t * r with r odd, and is a multiple of lambda(n)
+    let k: BigUint = e * d - &one;
+    if k.is_zero() || k.is_odd() {
+        return None;
+    }
+    let t = k.trailing_zeros().unwrap();
+    let r: BigUint = &k >> t;
+
+    const RECOVER_TRIES: u32 = 100;
+    for g in 2..RECOVER_TRIES + 2 {
+        let mut y: BigUint = BigUint::from(g).modpow(&r, n);
+        if y.is_one() || y == n_minus_one {
+            continue;
+        }
+        for _ in 0..t {
+            let x: BigUint = y.modpow(&BigUint::from(2_u32), n);
+            if x.is_one() {
+                // y is a nontrivial square root of 1 modulo n
+                let p: BigUint = (&y - &one).gcd(n);
+                let q: BigUint = n / &p;
+                return Some(if p > q { (p, q) } else { (q, p) });
+            }
+            if x == n_minus_one {
+                break;
+            }
+            y = x;
+        }
+    }
+    None
+}
+
+// Primes used for trial division, below 2^8
+const TRIAL_PRIMES: [u32; 54] = [
+    2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73, 79, 83, 89, 97,
+    101, 103, 107, 109, 113, 127, 131, 137, 139, 149, 151, 157, 163, 167, 173, 179, 181, 191, 193,
+    197, 199, 211, 223, 227, 229, 233, 239, 241, 251,
+];
+
+// Miller-Rabin with the first 13 prime bases (up to 41) is exact below this
+// bound, and with the first 12 below 3.18 * 10^23 > 2^64 (Sorenson and
+// Webster, 2015)
+const DETERMINISTIC_BOUND: u128 = 3_317_044_064_679_887_385_961_981;
 
-    if proposal.is_zero() || (proposal != &two && proposal.is_even()) {
+// Trial division decides n < 2^16, Miller-Rabin with fixed bases decides
+// n < 3.3 * 10^24, and larger n go through Baillie-PSW, which has no known
+// pseudoprime
+pub fn is_prime(proposal: &BigUint) -> bool {
+    if let Some(n) = proposal.to_u64() {
+        return is_prime_u64(n);
+    }
+    if TRIAL_PRIMES.iter().any(|&p| (proposal % p).is_zero()) {
         return false;
     }
+    if proposal.to_u128().is_some_and(|n| n < DETERMINISTIC_BOUND) {
+        return TRIAL_PRIMES[..13]
+            .iter()
+            .all(|&a| strong_probable_prime(proposal, &BigUint::from(a)));
+    }
+    baillie_psw(proposal)
+}
 
-    // less than 1/2 probability to find
-    if !fermat_test(proposal) {
+// Same as `is_prime` in machine words
+fn is_prime_u64(n: u64) -> bool {
+    if n < 2 {
         return false;
     }
+    for p in TRIAL_PRIMES.map(u64::from) {
+        if n.is_multiple_of(p) {
+            return n == p;
+        }
+    }
+    if n < 1 << 16 {
+        return true;
+    }
 
-    const MILLER_RABIN_THRES: u64 = 8;
-    miller_rabin_test(proposal, MILLER_RABIN_THRES)
+    let mul = |x: u64, y: u64| (x as u128 * y as u128 % n as u128) as u64;
+    let s = (n - 1).trailing_zeros();
+    let d = (n - 1) >> s;
+    TRIAL_PRIMES[..12].iter().all(|&a| {
+        // a^d mod n
+        let (mut x, mut base, mut e) = (1, a as u64, d);
+        while e > 0 {
+            if e.is_odd() {
+                x = mul(x, base);
+            }
+            base = mul(base, base);
+            e >>= 1;
+        }
+        if x == 1 || x == n - 1 {
+            return true;
+        }
+        (1..s).any(|_| {
+            x = mul(x, x);
+            x == n - 1
+        })
+    })
 }
 
-fn fermat_test(proposal: &BigUint) -> bool {
-    let mut rng = rand::thread_rng();
-
-    let a: BigUint = rng.gen_biguint_below(proposal);
-    a.modpow(&(proposal - BigUint::one()), proposal).is_one()
+// Strong probable prime test to base 2, then strong Lucas test
+// (FIPS 186-5 B.3.3), for odd n without small factors
+fn baillie_psw(n: &BigUint) -> bool {
+    strong_probable_prime(n, &BigUint::from(2_u32)) && strong_lucas_test(n)
 }
 
-fn miller_rabin_test(proposal: &BigUint, threshold: u64) -> bool {
-    let one: BigUint = BigUint::one();
-    let two: BigUint = &one + &one;
+// Strong probable prime test of odd n > 3 to base a: with n - 1 = 2^s * d and d
+// odd, a^d = 1 or a^(2^r * d) = -1 for some r < s
+fn strong_probable_prime(n: &BigUint, a: &BigUint) -> bool {
+    let one = BigUint::one();
+    let n_minus_one: BigUint = n - &one;
+    let s = n_minus_one.trailing_zeros().unwrap();
+    let d: BigUint = &n_minus_one >> s;
 
-    if proposal == &one {
-        return false;
-    } else if proposal == &two {
+    let mut x: BigUint = a.modpow(&d, n);
+    if x.is_one() || x == n_minus_one {
         return true;
-    } else if proposal.is_even() {
-        return false;
     }
+    for _ in 1..s {
+        x = &x * &x % n;
+        if x == n_minus_one {
+            return true;
+        } else if x.is_one() {
+            return false;
+        }
+    }
+    false
+}
 
-    let mut d: BigUint = proposal - &one;
-    let mut s: BigUint = BigUint::zero();
-    while d.is_even() {
-        d = d.div_floor(&two);
-        s += &one;
+// Jacobi symbol (a / n) for odd n
+fn jacobi(a: &BigUint, n: &BigUint) -> i32 {
+    let (mut a, mut n) = (a % n, n.clone());
+    let mut result = 1;
+    while !a.is_zero() {
+        let zeros = a.trailing_zeros().unwrap();
+        a >>= zeros;
+        // (2 / n) = -1 when n = 3 or 5 mod 8
+        let n_mod_8 = (&n % 8_u32).to_u32().unwrap();
+        if zeros.is_odd() && (n_mod_8 == 3 || n_mod_8 == 5) {
+            result = -result;
+        }
+        // quadratic reciprocity: the sign flips when both are 3 mod 4
+        if (&a % 4_u32).to_u32() == Some(3) && n_mod_8 % 4 == 3 {
+            result = -result;
+        }
+        (a, n) = (&n % &a, a);
+    }
+    if n.is_one() {
+        result
+    } else {
+        0
     }
+}
 
-    let step: u64 = (s - &one).to_u64().unwrap();
-    let mut rng = rand::thread_rng();
-    for _ in 0..threshold {
-        let a: BigUint = rng.gen_biguint_range(&two, &(proposal - &one));
-        let mut x: BigUint = a.modpow(&d, &proposal);
+// Integer of sign `negative` and magnitude `abs`, reduced modulo n
+fn signed_mod(abs: u64, negative: bool, n: &BigUint) -> BigUint {
+    let r = BigUint::from(abs) % n;
+    if negative && !r.is_zero() {
+        n - r
+    } else {
+        r
+    }
+}
 
-        if x.is_one() || x == (proposal - &one) {
-            continue;
+// Strong Lucas probable prime test with the parameters of Selfridge's method A:
+// D is the first of 5, -7, 9, -11, ... with (D / n) = -1, P = 1, Q = (1 - D) / 4
+fn strong_lucas_test(n: &BigUint) -> bool {
+    // a square never yields (D / n) = -1
+    if n.sqrt().pow(2) == *n {
+        return false;
+    }
+    let mut abs_d: u64 = 5;
+    let mut negative = false;
+    let d = loop {
+        let d = signed_mod(abs_d, negative, n);
+        match jacobi(&d, n) {
+            -1 => break d,
+            // D shares a factor with n, unless n = |D|
+            0 if BigUint::from(abs_d) != *n => return false,
+            _ => {}
         }
+        abs_d += 2;
+        negative = !negative;
+    };
+    // Q = (1 - D) / 4, where D = 1 mod 4
+    let q = if negative {
+        signed_mod((abs_d + 1) / 4, false, n)
+    } else {
+        signed_mod((abs_d - 1) / 4, true, n)
+    };
 
-        let mut flag: bool = false;
-        for _ in 0..step {
-            x = x.modpow(&two, proposal);
+    let half = |x: BigUint| {
+        let x = x % n;
+        if x.is_odd() {
+            (x + n) >> 1
+        } else {
+            x >> 1
+        }
+    };
+    let double_q = |q_k: &BigUint| (q_k << 1_u32) % n;
 
-            if x.is_one() {
-                return false;
-            } else if x == (proposal - &one) {
-                // probably still not prime, check multiple times
-                flag = true;
-                break;
-            }
+    // n + 1 = 2^s * k with k odd; U_k and V_k by a left-to-right ladder on k,
+    // with P = 1: U_2j = U_j * V_j, V_2j = V_j^2 - 2Q^j,
+    // U_j+1 = (U_j + V_j) / 2, V_j+1 = (D * U_j + V_j) / 2
+    let n_plus_one: BigUint = n + 1_u32;
+    let s = n_plus_one.trailing_zeros().unwrap();
+    let k: BigUint = &n_plus_one >> s;
+    let (mut u, mut v, mut q_k) = (BigUint::one(), BigUint::one(), q.clone());
+    for i in (0..k.bits() - 1).rev() {
+        u = &u * &v % n;
+        v = (&v * &v + n - double_q(&q_k)) % n;
+        q_k = &q_k * &q_k % n;
+        if k.bit(i) {
+            (u, v) = (half(&u + &v), half(&d * &u % n + &v));
+            q_k = &q_k * &q % n;
         }
+    }
 
-        if !flag {
-            return false;
+    // U_k = 0, or V_(2^r * k) = 0 for some r < s
+    if u.is_zero() || v.is_zero() {
+        return true;
+    }
+    for _ in 1..s {
+        v = (&v * &v + n - double_q(&q_k)) % n;
+        if v.is_zero() {
+            return true;
         }
+        q_k = &q_k * &q_k % n;
     }
-    true
+    false
+}
+
+// Miller-Rabin with `threshold` random bases in [2, n - 2] drawn from `rng`, for
+// the round counts that FIPS 186-5 prescribes
+pub(crate) fn miller_rabin_test<R: RngCore + ?Sized>(
+    rng: &mut R,
+    proposal: &BigUint,
+    threshold: u64,
+) -> bool {
+    let two = BigUint::from(2_u32);
+    if proposal < &BigUint::from(4_u32) {
+        return proposal >= &two;
+    } else if proposal.is_even() {
+        return false;
+    }
+
+    let high: BigUint = proposal - 1_u32;
+    (0..threshold).all(|_| strong_probable_prime(proposal, &rng.gen_biguint_range(&two, &high)))
+}
+
+#[cfg(test)]
+#[test]
+fn test_is_prime() {
+    use rand::SeedableRng;
+
+    // sieve of Eratosthenes
+    const LIMIT: usize = 1_000_000;
+    let mut sieve = vec![true; LIMIT];
+    sieve[0] = false;
+    sieve[1] = false;
+    for i in 2..LIMIT {
+        if sieve[i] {
+            for j in (i * i..LIMIT).step_by(i) {
+                sieve[j] = false;
+            }
+        }
+    }
+    for (n, &prime) in sieve.iter().enumerate() {
+        assert_eq!(is_prime(&BigUint::from(n)), prime, "n = {}", n);
+        if n > 5 && n < 100_000 && n.is_odd() {
+            assert_eq!(baillie_psw(&BigUint::from(n)), prime, "n = {}", n);
+        }
+    }
+
+    // strong pseudoprimes to the first 9, 12 and 13 prime bases
+    let composites = [
+        "3825123056546413051",
+        "318665857834031151167461",
+        "3317044064679887385961981",
+    ];
+    for n in composites {
+        assert!(!is_prime(&n.parse().unwrap()), "n = {}", n);
+    }
+    // strong Lucas pseudoprimes, caught by the base 2 test
+    for n in [
+        5459_u32, 5777, 10877, 16109, 18971, 22499, 24569, 25199, 40309, 58519,
+    ] {
+        assert!(strong_lucas_test(&BigUint::from(n)), "n = {}", n);
+        assert!(!baillie_psw(&BigUint::from(n)), "n = {}", n);
+    }
+    let mersenne = (BigUint::one() << 127_u32) - 1_u32;
+    assert!(is_prime(&mersenne));
+    assert!(!is_prime(
+        &(&mersenne * ((BigUint::one() << 89_u32) - 1_u32))
+    ));
+    let mut rng = rand::thread_rng();
+    assert!(miller_rabin_test(&mut rng, &BigUint::from(3_u32), 8));
+
+    // the bases come from the RNG passed in: one that only yields zeros always
+    // picks base 2, which 2047 = 23 * 89 fools
+    let spsp = BigUint::from(2047_u32);
+    assert!(miller_rabin_test(
+        &mut rand::rngs::mock::StepRng::new(0, 0),
+        &spsp,
+        8
+    ));
+    let mut seeded = rand::rngs::StdRng::seed_from_u64(18);
+    assert!(!miller_rabin_test(&mut seeded, &spsp, 8));
 }
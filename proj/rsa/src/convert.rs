@@ -0,0 +1,45 @@
+use crate::errors::{Error, Result};
+use num_bigint::BigUint;
+use num_traits::Zero;
+
+// Integer-to-Octet-String primitive (RFC 8017 4.1)
+// Big-endian encoding of x left-padded with zeros to exactly `size` octets
+pub fn i2osp(x: &BigUint, size: usize) -> Result<Vec<u8>> {
+    if x.is_zero() {
+        return Ok(vec![0_u8; size]);
+    }
+
+    let bytes: Vec<u8> = x.to_bytes_be();
+    if bytes.len() > size {
+        return Err(Error::IntergerTooLarge);
+    }
+
+    let mut output: Vec<u8> = vec![0_u8; size - bytes.len()];
+    output.extend_from_slice(&bytes);
+    Ok(output)
+}
+
+// Octet-String-to-Integer primitive (RFC 8017 4.2)
+// The empty octet string is the integer 0
+pub fn os2ip(x: &[u8]) -> Result<BigUint> {
+    Ok(BigUint::from_bytes_be(x))
+}
+
+pub fn string_xor(a: &[u8], b: &[u8]) -> Result<Vec<u8>> {
+    if a.len() != b.len() {
+        return Err(Error::InvalidBufferSize);
+    }
+
+    Ok(a.iter().zip(b.iter()).map(|(x, y)| x ^ y).collect())
+}
+
+#[cfg(test)]
+#[test]
+fn test_i2osp_os2ip() {
+    let x = BigUint::from(0x0102_u32);
+    assert_eq!(i2osp(&x, 4).unwrap(), vec![0x00, 0x00, 0x01, 0x02]);
+    assert_eq!(i2osp(&x, 1), Err(Error::IntergerTooLarge));
+    assert_eq!(i2osp(&BigUint::zero(), 2).unwrap(), vec![0x00, 0x00]);
+    assert_eq!(os2ip(&[0x00, 0x00, 0x01, 0x02]).unwrap(), x);
+    assert_eq!(os2ip(&[]).unwrap(), BigUint::zero());
+}
@@ -0,0 +1,234 @@
+// Minimal ASN.1 DER (X.690) encoding and decoding, for the key formats
+use num_bigint::BigUint;
+use zeroize::Zeroizing;
+
+use crate::errors::{Error, Result};
+
+pub const INTEGER: u8 = 0x02;
+pub const BIT_STRING: u8 = 0x03;
+pub const OCTET_STRING: u8 = 0x04;
+pub const NULL: u8 = 0x05;
+pub const OBJECT_IDENTIFIER: u8 = 0x06;
+pub const SEQUENCE: u8 = 0x30;
+
+// Content octets of the object identifiers used by the key formats
+pub const OID_RSA_ENCRYPTION: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x01];
+pub const OID_RSASSA_PSS: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x0a];
+pub const OID_MGF1: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x08];
+pub const OID_SHA1: &[u8] = &[0x2b, 0x0e, 0x03, 0x02, 0x1a];
+pub const OID_SHA224: &[u8] = &[0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x04];
+pub const OID_SHA256: &[u8] = &[0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x01];
+pub const OID_SHA384: &[u8] = &[0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x02];
+pub const OID_SHA512: &[u8] = &[0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x03];
+pub const OID_PBES2: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x05, 0x0d];
+pub const OID_PBKDF2: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x05, 0x0c];
+pub const OID_HMAC_WITH_SHA256: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x02, 0x09];
+pub const OID_SCRYPT: &[u8] = &[0x2b, 0x06, 0x01, 0x04, 0x01, 0xda, 0x47, 0x04, 0x0b];
+pub const OID_AES_256_CBC: &[u8] = &[0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x01, 0x2a];
+// common alternatives that are not supported, used by the tests
+pub const OID_HMAC_WITH_SHA1: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x02, 0x07];
+pub const OID_AES_128_CBC: &[u8] = &[0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x01, 0x02];
+
+// Tag, definite length and content
+pub fn encode(tag: u8, content: &[u8]) -> Vec<u8> {
+    let len = content.len();
+    // tag, length octets and content fit without a reallocation
+    let mut out = Vec::with_capacity(content.len() + 2 + std::mem::size_of::<usize>());
+    out.push(tag);
+    if len < 0x80 {
+        out.push(len as u8);
+    } else {
+        let len_bytes: Vec<u8> = len
+            .to_be_bytes()
+            .into_iter()
+            .skip_while(|&b| b == 0)
+            .collect();
+        out.push(0x80 | len_bytes.len() as u8);
+        out.extend_from_slice(&len_bytes);
+    }
+    out.extend_from_slice(content);
+    out
+}
+
+// Non-negative INTEGER, with a leading zero octet when the top bit is set
+// The temporaries are wiped, as the integer may be a private one
+pub fn integer(x: &BigUint) -> Vec<u8> {
+    let bytes = Zeroizing::new(x.to_bytes_be());
+    if bytes[0] & 0x80 != 0 {
+        let mut padded = Zeroizing::new(Vec::with_capacity(bytes.len() + 1));
+        padded.push(0);
+        padded.extend_from_slice(&bytes);
+        return encode(INTEGER, &padded);
+    }
+    encode(INTEGER, &bytes)
+}
+
+pub fn sequence(items: &[Vec<u8>]) -> Vec<u8> {
+    encode(SEQUENCE, &Zeroizing::new(items.concat()))
+}
+
+pub fn octet_string(content: &[u8]) -> Vec<u8> {
+    encode(OCTET_STRING, content)
+}
+
+// BIT STRING without unused bits
+pub fn bit_string(content: &[u8]) -> Vec<u8> {
+    encode(BIT_STRING, &[&[0x00], content].concat())
+}
+
+pub fn null() -> Vec<u8> {
+    encode(NULL, &[])
+}
+
+// `oid` holds the already encoded arcs, i.e. the content octets
+pub fn object_identifier(oid: &[u8]) -> Vec<u8> {
+    encode(OBJECT_IDENTIFIER, oid)
+}
+
+// Sequential reader over the elements of a DER encoding
+pub struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Reader { data }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    pub fn peek_tag(&self) -> Option<u8> {
+        self.data.first().copied()
+    }
+
+    // Next element, which must carry `tag`; returns its content octets
+    pub fn read(&mut self, tag: u8) -> Result<&'a [u8]> {
+        if self.data.len() < 2 || self.data[0] != tag {
+            return Err(Error::MalformedEncoding);
+        }
+
+        let (len, header) = match self.data[1] {
+            len @ 0x00..=0x7f => (len as usize, 2),
+            0x80 => return Err(Error::MalformedEncoding),
+            first => {
+                let count = (first & 0x7f) as usize;
+                if count > std::mem::size_of::<usize>() || self.data.len() < 2 + count {
+                    return Err(Error::MalformedEncoding);
+                }
+                let len_bytes = &self.data[2..2 + count];
+                // DER requires the shortest length encoding
+                if len_bytes[0] == 0 || (count == 1 && len_bytes[0] < 0x80) {
+                    return Err(Error::MalformedEncoding);
+                }
+                let len = len_bytes
+                    .iter()
+                    .fold(0_usize, |acc, &b| (acc << 8) | b as usize);
+                (len, 2 + count)
+            }
+        };
+
+        if self.data.len() - header < len {
+            return Err(Error::MalformedEncoding);
+        }
+        let content = &self.data[header..header + len];
+        self.data = &self.data[header + len..];
+        Ok(content)
+    }
+
+    pub fn read_sequence(&mut self) -> Result<Reader<'a>> {
+        Ok(Reader::new(self.read(SEQUENCE)?))
+    }
+
+    // Non-negative, minimally encoded INTEGER
+    pub fn read_integer(&mut self) -> Result<BigUint> {
+        let content = self.read(INTEGER)?;
+        if content.is_empty() || content[0] & 0x80 != 0 {
+            return Err(Error::MalformedEncoding);
+        }
+        if content.len() > 1 && content[0] == 0 && content[1] & 0x80 == 0 {
+            return Err(Error::MalformedEncoding);
+        }
+        Ok(BigUint::from_bytes_be(content))
+    }
+
+    pub fn read_small_integer(&mut self) -> Result<u64> {
+        let x = self.read_integer()?;
+        if x.bits() > 64 {
+            return Err(Error::MalformedEncoding);
+        }
+        Ok(x.iter_u64_digits().next().unwrap_or(0))
+    }
+
+    pub fn read_octet_string(&mut self) -> Result<&'a [u8]> {
+        self.read(OCTET_STRING)
+    }
+
+    pub fn read_bit_string(&mut self) -> Result<&'a [u8]> {
+        match self.read(BIT_STRING)? {
+            [0x00, content @ ..] => Ok(content),
+            _ => Err(Error::MalformedEncoding),
+        }
+    }
+
+    pub fn read_null(&mut self) -> Result<()> {
+        match self.read(NULL)? {
+            [] => Ok(()),
+            _ => Err(Error::MalformedEncoding),
+        }
+    }
+
+    pub fn read_object_identifier(&mut self) -> Result<&'a [u8]> {
+        self.read(OBJECT_IDENTIFIER)
+    }
+
+    // Trailing data after the last expected element is an error
+    pub fn finish(self) -> Result<()> {
+        if self.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::MalformedEncoding)
+        }
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn test_der_round_trip() {
+    let big = BigUint::from_bytes_be(&[0xff; 300]);
+    let encoded = sequence(&[
+        integer(&BigUint::from(0_u32)),
+        integer(&BigUint::from(0x80_u32)),
+        integer(&big),
+        null(),
+    ]);
+    assert_eq!(
+        &encoded[..12],
+        &[0x30, 0x82, 0x01, 0x3a, 0x02, 0x01, 0x00, 0x02, 0x02, 0x00, 0x80, 0x02]
+    );
+
+    let mut outer = Reader::new(&encoded);
+    let mut seq = outer.read_sequence().unwrap();
+    assert_eq!(seq.read_small_integer(), Ok(0));
+    assert_eq!(seq.read_small_integer(), Ok(0x80));
+    assert_eq!(seq.read_integer(), Ok(big));
+    assert_eq!(seq.read_null(), Ok(()));
+    assert_eq!(seq.finish(), Ok(()));
+    assert_eq!(outer.finish(), Ok(()));
+
+    // non-minimal lengths and integers, negative integers and truncation
+    for bad in [
+        &[0x02, 0x81, 0x01, 0x01][..],
+        &[0x02, 0x02, 0x00, 0x01],
+        &[0x02, 0x01, 0x80],
+        &[0x02, 0x00],
+        &[0x02, 0x03, 0x01],
+        &[0x02, 0x80, 0x01, 0x00, 0x00],
+    ] {
+        assert_eq!(
+            Reader::new(bad).read_integer(),
+            Err(Error::MalformedEncoding)
+        );
+    }
+}
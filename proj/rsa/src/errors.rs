@@ -4,27 +4,83 @@ pub type Result<T> = core::result::Result<T, Error>;
 #[non_exhaustive]
 pub enum Error {
     MessageNotASCII,
-    InvalidKeyPair,
+    // the reason names the check that failed
+    InvalidKeyPair(&'static str),
     MaskTooLong,
     MessageTooLong,
     IntergerTooLarge,
     OctetStringEmpty,
     InvalidBufferSize,
     DecryptionError,
+    RepresentativeOutOfRange,
+    EncodingError,
+    VerificationError,
+    MessageNotUTF8,
+    FaultDetected,
+    MalformedEncoding,
+    WrongPassword,
+    UnsupportedAlgorithm,
+    ExpiredToken,
+    ImmatureToken,
+    InvalidIssuer,
+    InvalidAudience,
+    Io(std::io::ErrorKind),
+    WrongKeyType,
+    TruncatedFile,
+    InvalidKeySize,
+    InvalidExponent,
+    PrimeGenerationFailed,
+    SeedTooShort,
+    InvalidPrimeCount,
+    PasswordRequired,
+    // the registered claim that a validation requires
+    MissingClaim(&'static str),
 }
 
 impl std::error::Error for Error {}
+
+// Only the kind is kept, so that errors stay comparable
+impl From<std::io::Error> for Error {
+    fn from(error: std::io::Error) -> Self {
+        Error::Io(error.kind())
+    }
+}
+
 impl core::fmt::Display for Error {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let msg: &str = match self {
             Error::MessageNotASCII => "Message is not ASCII.",
-            Error::InvalidKeyPair => "Invalid public key and secret key pair.",
+            Error::InvalidKeyPair(reason) => {
+                return write!(f, "Invalid public key and secret key pair: {}.", reason)
+            }
             Error::MaskTooLong => "The mask is too long",
             Error::IntergerTooLarge => "Integer too large",
             Error::OctetStringEmpty => "The octet string is empty",
             Error::MessageTooLong => "The message is too long",
             Error::InvalidBufferSize => "Invalid buffer size",
             Error::DecryptionError => "Fail to decrypt",
+            Error::RepresentativeOutOfRange => "The representative is out of range",
+            Error::EncodingError => "Fail to encode",
+            Error::VerificationError => "Invalid signature",
+            Error::MessageNotUTF8 => "Message is not valid UTF-8.",
+            Error::FaultDetected => "Fault detected in the CRT computation",
+            Error::MalformedEncoding => "Malformed key encoding",
+            Error::WrongPassword => "Wrong password or corrupted encrypted key",
+            Error::UnsupportedAlgorithm => "Unsupported or unexpected algorithm",
+            Error::ExpiredToken => "The token has expired",
+            Error::ImmatureToken => "The token is not valid yet",
+            Error::InvalidIssuer => "Invalid token issuer",
+            Error::InvalidAudience => "Invalid token audience",
+            Error::Io(_) => "I/O error on the key file",
+            Error::WrongKeyType => "Wrong key type",
+            Error::TruncatedFile => "The key file is truncated",
+            Error::InvalidKeySize => "Unsupported key size",
+            Error::InvalidExponent => "Invalid public exponent",
+            Error::PrimeGenerationFailed => "Fail to generate the primes",
+            Error::SeedTooShort => "The seed is too short for the security strength",
+            Error::InvalidPrimeCount => "Unsupported number of primes for the key size",
+            Error::PasswordRequired => "The key is encrypted and needs a password",
+            Error::MissingClaim(name) => return write!(f, "The token has no {} claim", name),
         };
         f.write_str(msg)
     }
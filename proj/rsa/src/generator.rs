@@ -1,100 +1,440 @@
-use base64::{engine::general_purpose, Engine};
-use num::{One, Signed};
-use num_bigint::{BigInt, BigUint, RandBigInt, Sign};
-use rand;
-use std::{fmt::Display, fs::File, io::Write, str, thread};
+use num::{Integer, One};
+use num_bigint::BigUint;
+use rand::{self, CryptoRng, RngCore};
+use std::{
+    fmt::Display,
+    fs::{File, OpenOptions},
+    io::Write,
+    path::Path,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+    thread,
+};
 
 use crate::{
-    algorithms::{ext_euc, is_prime},
-    plain_rsa::{PublicKey, PrivateKey},
-    errors::Result,
+    algorithms::mod_inverse,
+    errors::{Error, Result},
+    pbes2,
+    plain_rsa::{PrivateKey, PublicKey},
+    primes::{self, PrimeSearch},
+    secret::Secret,
 };
+use zeroize::Zeroizing;
 
+// The secret members are wiped on drop, and neither `Debug` nor `Display` shows them
+#[derive(Debug)]
 pub struct Generator {
     pub n: BigUint,
     pub e: BigUint,
-    pub d: BigUint,
+    pub d: Secret,
+    pub p: Secret,
+    pub q: Secret,
+    // primes beyond p and q of a multi-prime key
+    pub other_primes: Vec<Secret>,
 }
 
 impl Display for Generator {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "n: {}, e: {}, d: {}", &self.n, &self.e, &self.d)
+        write!(f, "n: {}, e: {}, d: {:?}", &self.n, &self.e, &self.d)
+    }
+}
+
+impl TryFrom<(&PublicKey, &PrivateKey)> for Generator {
+    type Error = Error;
+
+    fn try_from((pk, sk): (&PublicKey, &PrivateKey)) -> Result<Self> {
+        sk.validate_pair(pk)?;
+        Ok(Generator {
+            n: pk.n.clone(),
+            e: pk.e.clone(),
+            d: sk.d.clone(),
+            p: sk.p.clone(),
+            q: sk.q.clone(),
+            other_primes: sk
+                .other_primes
+                .iter()
+                .map(|info| info.prime.clone())
+                .collect(),
+        })
+    }
+}
+
+// Smallest modulus the builder produces; see `validate::MIN_MODULUS_BITS` for
+// what is acceptable in production
+const MIN_BUILDER_BITS: u64 = 64;
+
+// Largest number of primes for a modulus of `bits` bits, as in OpenSSL: more
+// primes of fewer bits would make n easier to factor with ECM than with NFS
+fn max_primes(bits: u64) -> usize {
+    match bits {
+        8192.. => 5,
+        4096.. => 4,
+        1024.. => 3,
+        _ => 2,
+    }
+}
+
+// Key generation parameters; the defaults are a 2048-bit modulus, e = 65537,
+// two primes and a single thread
+pub struct GeneratorBuilder {
+    bits: u64,
+    e: BigUint,
+    search: PrimeSearch,
+    primes: usize,
+    threads: usize,
+}
+
+impl Default for GeneratorBuilder {
+    fn default() -> Self {
+        GeneratorBuilder {
+            bits: 2048,
+            e: BigUint::from(65537_u32),
+            search: PrimeSearch::default(),
+            primes: 2,
+            threads: 1,
+        }
     }
 }
 
-impl From<(&PublicKey, &PrivateKey)> for Generator {
-    fn from((pk, sk): (&PublicKey, &PrivateKey)) -> Self {
-        if pk.n != sk.n {
-            panic!("Invalid public key and secret key pair.")
-        } else {
-            Generator {
-                n: pk.n.clone(),
-                e: pk.e.clone(),
-                d: sk.d.clone(),
+impl GeneratorBuilder {
+    // Exact bit length of the modulus n
+    pub fn bits(mut self, bits: u64) -> Self {
+        self.bits = bits;
+        self
+    }
+
+    // Public exponent, which must be odd and at least 3
+    pub fn exponent(mut self, e: &BigUint) -> Self {
+        self.e = e.clone();
+        self
+    }
+
+    pub fn prime_search(mut self, search: PrimeSearch) -> Self {
+        self.search = search;
+        self
+    }
+
+    // Number of prime factors of n; multi-prime keys speed up the private
+    // operations, but are outside of FIPS 186-5
+    pub fn primes(mut self, count: usize) -> Self {
+        self.primes = count;
+        self
+    }
+
+    // Worker threads testing candidates for each prime in `build`, which stop as
+    // soon as one of them finds it; `build_batch` runs one key per thread instead
+    pub fn threads(mut self, count: usize) -> Self {
+        self.threads = count.max(1);
+        self
+    }
+
+    pub fn build(&self) -> Result<Generator> {
+        if self.threads == 1 {
+            return self.build_with_rng(&mut rand::thread_rng());
+        }
+        self.check()?;
+
+        let rounds = primes::miller_rabin_rounds(self.bits);
+        Ok(self.generate(&mut rand::thread_rng(), |_, bits, other| {
+            Some(primes::parallel_prime(
+                self.search,
+                self.threads,
+                bits,
+                &self.e,
+                rounds,
+                other,
+            ))
+        }))
+    }
+
+    // FIPS 186-5 A.1.3 probable primes, unless the sieve search is chosen; the
+    // same RNG state always yields the same key, so this runs on one thread only
+    pub fn build_with_rng<R: CryptoRng + RngCore>(&self, rng: &mut R) -> Result<Generator> {
+        self.check()?;
+
+        let rounds = primes::miller_rabin_rounds(self.bits);
+        let search = match self.search {
+            PrimeSearch::Fips => primes::probable_prime::<R>,
+            PrimeSearch::Sieve => primes::sieve_prime::<R>,
+        };
+        Ok(self.generate(rng, |rng, bits, other| {
+            search(rng, bits, &self.e, rounds, other)
+        }))
+    }
+
+    // `count` key pairs, generated concurrently on the worker threads with one
+    // key per thread at a time; `progress(done, count)` is called as each key is
+    // done, from the thread that made it
+    pub fn build_batch<F: Fn(usize, usize) + Sync>(
+        &self,
+        count: usize,
+        progress: F,
+    ) -> Result<Vec<Generator>> {
+        self.check()?;
+
+        let next = AtomicUsize::new(0);
+        let done = Mutex::new(0);
+        thread::scope(|scope| {
+            let workers: Vec<_> = (0..self.threads.min(count))
+                .map(|_| {
+                    scope.spawn(|| -> Result<Vec<Generator>> {
+                        let mut rng = rand::thread_rng();
+                        let mut keys = vec![];
+                        while next.fetch_add(1, Ordering::Relaxed) < count {
+                            keys.push(self.build_with_rng(&mut rng)?);
+                            // the lock keeps the reported counts in order
+                            let mut done = done.lock().unwrap();
+                            *done += 1;
+                            progress(*done, count);
+                        }
+                        Ok(keys)
+                    })
+                })
+                .collect();
+            let mut keys = vec![];
+            for worker in workers {
+                keys.extend(worker.join().unwrap()?);
+            }
+            Ok(keys)
+        })
+    }
+
+    // p and q, or the primes of a multi-prime key, from `find`, until they make
+    // a valid key pair
+    fn generate<R, F>(&self, rng: &mut R, mut find: F) -> Generator
+    where
+        R: CryptoRng + RngCore,
+        F: FnMut(&mut R, u64, Option<&BigUint>) -> Option<BigUint>,
+    {
+        if self.primes > 2 {
+            return self.multi_prime(rng, find);
+        }
+
+        // p gets the extra bit of an odd length
+        let p_bits = self.bits.div_ceil(2);
+        let q_bits = self.bits / 2;
+        loop {
+            let Some(p) = find(rng, p_bits, None) else {
+                continue;
+            };
+            // |p - q| > 2^(nlen/2 - 100), or n is open to Fermat factoring
+            let Some(q) = find(rng, q_bits, Some(&p)) else {
+                continue;
+            };
+            if let Some(gen) = self.key_pair(vec![p, q]) {
+                return gen;
+            }
+        }
+    }
+
+    // The first primes get nlen / count bits each, and the last one is picked
+    // in the range where n gets exactly nlen bits
+    fn multi_prime<R, F>(&self, rng: &mut R, mut find: F) -> Generator
+    where
+        R: CryptoRng + RngCore,
+        F: FnMut(&mut R, u64, Option<&BigUint>) -> Option<BigUint>,
+    {
+        let one = BigUint::one();
+        let bits = self.bits / self.primes as u64;
+        loop {
+            let mut factors: Vec<BigUint> = vec![];
+            while factors.len() < self.primes - 1 {
+                if let Some(r) = find(rng, bits, None) {
+                    if !factors.contains(&r) {
+                        factors.push(r);
+                    }
+                }
+            }
+
+            // 2^(nlen - 1) <= r * product < 2^nlen
+            let product: BigUint = factors.iter().product();
+            let low = (&one << (self.bits - 1)).div_ceil(&product);
+            let high = ((&one << self.bits) - &one) / &product + &one;
+            let Some(r) = primes::random_prime_in_range(rng, &low, &high) else {
+                continue;
+            };
+            if !(&r - &one).gcd(&self.e).is_one() || factors.contains(&r) {
+                continue;
             }
+            factors.push(r);
+            if let Some(gen) = self.key_pair(factors) {
+                return gen;
+            }
+        }
+    }
+
+    // FIPS 186-5 A.1.2 provable primes, derived from `seed` only: auditors holding
+    // the seed can rerun the generation and compare. The modulus length has to be
+    // even, the exponent in (2^16, 2^256), and the seed at least twice as long as
+    // the security strength of the key.
+    pub fn build_provable(&self, seed: &[u8]) -> Result<Generator> {
+        self.check()?;
+        if self.primes != 2 {
+            return Err(Error::InvalidPrimeCount);
+        }
+        if self.bits.is_odd() {
+            return Err(Error::InvalidKeySize);
+        }
+        if self.e.bits() <= 16 || self.e.bits() > 256 {
+            return Err(Error::InvalidExponent);
+        }
+        if 8 * (seed.len() as u64) < 2 * security_strength(self.bits) {
+            return Err(Error::SeedTooShort);
+        }
+
+        let (p, q) = primes::provable_primes(self.bits / 2, &self.e, seed)
+            .ok_or(Error::PrimeGenerationFailed)?;
+        self.key_pair(vec![p, q])
+            .ok_or(Error::PrimeGenerationFailed)
+    }
+
+    fn check(&self) -> Result<()> {
+        if self.bits < MIN_BUILDER_BITS {
+            return Err(Error::InvalidKeySize);
         }
+        if self.e < BigUint::from(3_u32) || self.e.is_even() || self.e.bits() >= self.bits {
+            return Err(Error::InvalidExponent);
+        }
+        if self.primes < 2 || self.primes > max_primes(self.bits) {
+            return Err(Error::InvalidPrimeCount);
+        }
+        Ok(())
+    }
+
+    // d = e^-1 mod lambda(n), which has to exceed 2^(nlen/2) (FIPS 186-5 A.1.1)
+    fn key_pair(&self, factors: Vec<BigUint>) -> Option<Generator> {
+        let one = BigUint::one();
+        let lambda_n = factors
+            .iter()
+            .fold(one.clone(), |lambda, r| lambda.lcm(&(r - &one)));
+        let d = Secret::new(mod_inverse(&self.e, &lambda_n)?);
+        if d.bits() <= self.bits / 2 {
+            return None;
+        }
+        let mut factors = factors.into_iter().map(Secret::new);
+        let (p, q) = (factors.next()?, factors.next()?);
+        let other_primes: Vec<Secret> = factors.collect();
+        Some(Generator {
+            n: other_primes.iter().fold(&*p * &*q, |n, r| n * &**r),
+            e: self.e.clone(),
+            d,
+            p,
+            q,
+            other_primes,
+        })
+    }
+}
+
+// Security strength of an RSA modulus in bits (NIST SP 800-57 Part 1, Table 2)
+fn security_strength(bits: u64) -> u64 {
+    match bits {
+        15360.. => 256,
+        7680.. => 192,
+        3072.. => 128,
+        2048.. => 112,
+        _ => 80,
     }
 }
 
 impl Generator {
-    pub fn new(size: u64) -> Result<Self> {
-        let q_spawn = thread::spawn(move || Self::new_prime(size));
-        let p: BigUint = Self::new_prime(size);
-        let q: BigUint = q_spawn.join().unwrap();
-        let n: BigUint = &p * &q;
-        let fi_n: BigUint = (&p - BigUint::one()) * (&q - BigUint::one());
-
-        let e: BigUint = BigUint::from(65537 as u64);
-        let (mut s, _, _) = ext_euc(
-            &BigInt::from_biguint(Sign::Plus, e.clone()),
-            &BigInt::from_biguint(Sign::Plus, fi_n.clone()),
-        );
-
-        while s.is_negative() {
-            s += BigInt::from_biguint(Sign::Plus, fi_n.clone());
-        }
-        let d: BigUint = s.to_biguint().unwrap();
+    pub fn builder() -> GeneratorBuilder {
+        GeneratorBuilder::default()
+    }
 
-        Ok(Generator { n, e, d })
+    // p, q, then the other primes
+    pub fn primes(&self) -> Vec<&BigUint> {
+        [&self.p, &self.q]
+            .into_iter()
+            .chain(&self.other_primes)
+            .map(|r| &**r)
+            .collect()
     }
 
+    // `size` is the bit length of each prime, so n has 2 * size bits; the primes
+    // come from the sieve search on all cores, use the builder for FIPS 186-5 keys
+    pub fn new(size: u64) -> Result<Self> {
+        let cores = thread::available_parallelism().map_or(1, |n| n.get());
+        Generator::builder()
+            .bits(2 * size)
+            .prime_search(PrimeSearch::Sieve)
+            .threads(cores)
+            .build()
+    }
+
+    // Probable prime of exactly `size` bits, at least sqrt(2) * 2^(size - 1), from
+    // the sieve search
     pub fn new_prime(size: u64) -> BigUint {
-        let mut rng = rand::thread_rng();
+        Self::new_prime_with_rng(&mut rand::thread_rng(), size, &BigUint::one())
+    }
+
+    // Same as `new_prime`, with gcd(p - 1, e) = 1 so that e is invertible
+    pub fn new_prime_with_rng<R: CryptoRng + RngCore>(
+        rng: &mut R,
+        size: u64,
+        e: &BigUint,
+    ) -> BigUint {
+        let rounds = primes::miller_rabin_rounds(2 * size);
         loop {
-            let proposal: BigUint = rng.gen_biguint(size);
-            if is_prime(&proposal) == true {
-                return proposal;
+            if let Some(p) = primes::sieve_prime(rng, size, e, rounds, None) {
+                return p;
             }
         }
     }
 
+    // Writes the key pair as PKCS #1 PEM, readable by `openssl rsa`
     pub fn print_to_file(&self) -> Result<()> {
-        let mut pk_file = File::create("rsa_pk.key").unwrap();
-        let mut sk_file = File::create("rsa_sk.key").unwrap();
-
-        let encode =
-            |msg: &BigUint| general_purpose::STANDARD.encode(msg.to_owned().to_radix_be(16u32));
-
-        let (mut pk, mut sk) = (String::new(), String::new());
-        pk.push_str("---------- BEGIN RSA PUBLIC KEY ----------");
-        pk.push_str("\n");
-        pk.push_str(str::from_utf8(encode(&self.n).as_bytes()).unwrap());
-        pk.push_str("\n");
-        pk.push_str(str::from_utf8(encode(&self.e).as_bytes()).unwrap());
-        pk.push_str("\n");
-        pk.push_str("----------- END RSA PUBLIC KEY -----------");
-
-        sk.push_str("---------- BEGIN RSA PRIVATE KEY ----------");
-        sk.push_str("\n");
-        sk.push_str(str::from_utf8(encode(&self.n).as_bytes()).unwrap());
-        sk.push_str("\n");
-        sk.push_str(str::from_utf8(encode(&self.d).as_bytes()).unwrap());
-        sk.push_str("\n");
-        sk.push_str("----------- END RSA PRIVATE KEY -----------");
-
-        pk_file.write_all(pk.as_bytes()).unwrap();
-        sk_file.write_all(sk.as_bytes()).unwrap();
+        self.print_to_dir(Path::new("."))
+    }
+
+    // Same as `print_to_file`, with the private key as password-encrypted PKCS #8
+    pub fn print_to_file_encrypted(&self, password: &[u8]) -> Result<()> {
+        self.print_to_dir_encrypted(Path::new("."), password)
+    }
+
+    // Same as `print_to_file`, into rsa_pk.key and rsa_sk.key under `dir`
+    pub fn print_to_dir(&self, dir: &Path) -> Result<()> {
+        let sk = Zeroizing::new(PrivateKey::try_from(self)?.to_pkcs1_pem());
+        self.write_key_files(dir, &sk)
+    }
+
+    // Same as `print_to_file_encrypted`, into rsa_pk.key and rsa_sk.key under `dir`
+    pub fn print_to_dir_encrypted(&self, dir: &Path, password: &[u8]) -> Result<()> {
+        let params = pbes2::Parameters::generate(&mut rand::thread_rng());
+        let sk =
+            Zeroizing::new(PrivateKey::try_from(self)?.to_pkcs8_encrypted_pem(&params, password)?);
+        self.write_key_files(dir, &sk)
+    }
+
+    fn write_key_files(&self, dir: &Path, sk: &str) -> Result<()> {
+        let pk = PublicKey::from(self).to_pkcs1_pem();
+        File::create(dir.join("rsa_pk.key"))?.write_all(pk.as_bytes())?;
+        open_private(&dir.join("rsa_sk.key"))?.write_all(sk.as_bytes())?;
         Ok(())
     }
 }
 
+// The private key file is readable by its owner only, also when it already existed
+#[cfg(unix)]
+fn open_private(path: &Path) -> Result<File> {
+    use std::{fs::Permissions, os::unix::fs::OpenOptionsExt, os::unix::fs::PermissionsExt};
+
+    let file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(path)?;
+    file.set_permissions(Permissions::from_mode(0o600))?;
+    Ok(file)
+}
+
+#[cfg(not(unix))]
+fn open_private(path: &Path) -> Result<File> {
+    Ok(OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(path)?)
+}
@@ -0,0 +1,215 @@
+// JSON Web Keys (RFC 7517) for RSA (RFC 7518 6.3) and JWK thumbprints (RFC 7638)
+use std::fmt::Debug;
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use num::One;
+use num_bigint::BigUint;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use zeroize::Zeroizing;
+
+use crate::{
+    errors::{Error, Result},
+    plain_rsa::{PrivateKey, PublicKey},
+    secret::Secret,
+};
+
+// The integers are base64url encoded big-endian octets without padding. A Jwk
+// is the plaintext wire form of the key: its private members are plain strings
+// that are not wiped on drop, only redacted from Debug.
+#[derive(Clone, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Jwk {
+    pub kty: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub kid: Option<String>,
+    #[serde(rename = "use", skip_serializing_if = "Option::is_none")]
+    pub key_use: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub alg: Option<String>,
+    pub n: String,
+    pub e: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub d: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub p: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub q: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dp: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dq: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub qi: Option<String>,
+    // primes beyond p and q of a multi-prime key (RFC 7518 6.3.2.7)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub oth: Option<Vec<JwkOtherPrime>>,
+}
+
+// A prime r with its CRT exponent d and coefficient t
+#[derive(Clone, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct JwkOtherPrime {
+    pub r: String,
+    pub d: String,
+    pub t: String,
+}
+
+fn redacted<T>(member: &Option<T>) -> Option<&'static str> {
+    member.as_ref().map(|_| "[REDACTED]")
+}
+
+impl Debug for Jwk {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Jwk")
+            .field("kty", &self.kty)
+            .field("kid", &self.kid)
+            .field("key_use", &self.key_use)
+            .field("alg", &self.alg)
+            .field("n", &self.n)
+            .field("e", &self.e)
+            .field("d", &redacted(&self.d))
+            .field("p", &redacted(&self.p))
+            .field("q", &redacted(&self.q))
+            .field("dp", &redacted(&self.dp))
+            .field("dq", &redacted(&self.dq))
+            .field("qi", &redacted(&self.qi))
+            .field("oth", &self.oth)
+            .finish()
+    }
+}
+
+impl Debug for JwkOtherPrime {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("[REDACTED]")
+    }
+}
+
+// The octets are wiped, as the integer may be a private one
+fn encode_uint(x: &BigUint) -> String {
+    URL_SAFE_NO_PAD.encode(Zeroizing::new(x.to_bytes_be()))
+}
+
+fn decode_uint(s: &str) -> Result<BigUint> {
+    let bytes = URL_SAFE_NO_PAD
+        .decode(s)
+        .map_err(|_| Error::MalformedEncoding)?;
+    if bytes.is_empty() {
+        return Err(Error::MalformedEncoding);
+    }
+    Ok(BigUint::from_bytes_be(&bytes))
+}
+
+impl Jwk {
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+
+    pub fn from_json(json: &str) -> Result<Self> {
+        serde_json::from_str(json).map_err(|_| Error::MalformedEncoding)
+    }
+}
+
+impl PublicKey {
+    pub fn to_jwk(&self) -> Jwk {
+        Jwk {
+            kty: "RSA".to_owned(),
+            n: encode_uint(&self.n),
+            e: encode_uint(&self.e),
+            ..Default::default()
+        }
+    }
+
+    // Private members, if any, are ignored
+    pub fn from_jwk(jwk: &Jwk) -> Result<Self> {
+        if jwk.kty != "RSA" {
+            return Err(Error::WrongKeyType);
+        }
+        PublicKey::new(&decode_uint(&jwk.n)?, &decode_uint(&jwk.e)?)
+    }
+
+    // RFC 7638 thumbprint: SHA-256 over the required members in lexicographic
+    // order, without whitespace
+    pub fn jwk_thumbprint(&self) -> String {
+        let canonical = format!(
+            r#"{{"e":"{}","kty":"RSA","n":"{}"}}"#,
+            encode_uint(&self.e),
+            encode_uint(&self.n)
+        );
+        URL_SAFE_NO_PAD.encode(Sha256::digest(canonical.as_bytes()))
+    }
+}
+
+impl PrivateKey {
+    pub fn to_jwk(&self) -> Jwk {
+        Jwk {
+            kty: "RSA".to_owned(),
+            n: encode_uint(&self.n),
+            e: encode_uint(&self.e),
+            d: Some(encode_uint(&self.d)),
+            p: Some(encode_uint(&self.p)),
+            q: Some(encode_uint(&self.q)),
+            dp: Some(encode_uint(&self.dp)),
+            dq: Some(encode_uint(&self.dq)),
+            qi: Some(encode_uint(&self.qinv)),
+            oth: (!self.other_primes.is_empty()).then(|| {
+                self.other_primes
+                    .iter()
+                    .map(|info| JwkOtherPrime {
+                        r: encode_uint(&info.prime),
+                        d: encode_uint(&info.exponent),
+                        t: encode_uint(&info.coefficient),
+                    })
+                    .collect()
+            }),
+            ..Default::default()
+        }
+    }
+
+    // The CRT members are optional as a whole (RFC 7518 6.3.2); when present they
+    // have to match the ones derived from n, e, d and the primes, and `oth` lists
+    // the primes beyond p and q
+    pub fn from_jwk(jwk: &Jwk) -> Result<Self> {
+        let pk = PublicKey::from_jwk(jwk)?;
+        // the private members are wiped as soon as the key is built
+        let secret = |x: &str| decode_uint(x).map(Secret::new);
+        let d = secret(jwk.d.as_deref().ok_or(Error::MalformedEncoding)?)?;
+        let oth = jwk.oth.as_deref().unwrap_or_default();
+        if jwk.oth.is_some() && oth.is_empty() {
+            return Err(Error::MalformedEncoding);
+        }
+
+        match (&jwk.p, &jwk.q, &jwk.dp, &jwk.dq, &jwk.qi) {
+            (None, None, None, None, None) if oth.is_empty() => PrivateKey::new(&pk.n, &pk.e, &d),
+            (Some(p), Some(q), Some(dp), Some(dq), Some(qi)) => {
+                let mut primes = vec![secret(p)?, secret(q)?];
+                for other in oth {
+                    primes.push(secret(&other.r)?);
+                }
+                let primes: Vec<&BigUint> = primes.iter().map(|r| &**r).collect();
+                let sk = PrivateKey::from_primes(&pk.n, &pk.e, &d, &primes)?;
+                let mut consistent =
+                    sk.dp == secret(dp)? && sk.dq == secret(dq)? && sk.qinv == secret(qi)?;
+                for (info, other) in sk.other_primes.iter().zip(oth) {
+                    consistent &=
+                        info.exponent == secret(&other.d)? && info.coefficient == secret(&other.t)?;
+                }
+                if !consistent {
+                    return Err(Error::InvalidKeyPair("inconsistent CRT parameters"));
+                }
+                // e * d_i = 1 mod (r_i - 1) for every prime, so d is an inverse of e
+                let one = BigUint::one();
+                let exponents = [(&sk.p, &sk.dp), (&sk.q, &sk.dq)].into_iter().chain(
+                    sk.other_primes
+                        .iter()
+                        .map(|info| (&info.prime, &info.exponent)),
+                );
+                for (r, d_r) in exponents {
+                    if (&sk.e * &**d_r) % (&**r - &one) != one {
+                        return Err(Error::InvalidKeyPair("e * d is not 1 mod lambda(n)"));
+                    }
+                }
+                Ok(sk)
+            }
+            _ => Err(Error::InvalidKeyPair("incomplete CRT parameters")),
+        }
+    }
+}
@@ -1,4 +1,21 @@
 pub mod errors;
 pub mod algorithms;
+pub mod convert;
 pub mod generator;
-pub mod plain_rsa;
\ No newline at end of file
+pub mod plain_rsa;
+pub mod montgomery;
+pub mod mgf;
+pub mod oaep;
+pub mod pss;
+pub mod pkcs1v15;
+pub mod der;
+pub mod pem;
+pub mod pkcs1;
+pub mod pkcs8;
+pub mod pbes2;
+pub mod ssh;
+pub mod jwk;
+pub mod jws;
+pub mod validate;
+pub mod primes;
+pub mod secret;
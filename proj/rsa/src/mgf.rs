@@ -0,0 +1,39 @@
+// Mask generation functions (RFC 8017 B.2)
+use sha2::Digest;
+
+use crate::errors::{Error, Result};
+
+// MGF1 (RFC 8017 B.2.1), parameterized by its underlying hash function
+pub fn mgf1<D: Digest>(seed: &[u8], mask_len: usize) -> Result<Vec<u8>> {
+    let h_len = <D as Digest>::output_size();
+    if mask_len as u64 > (1_u64 << 32) * h_len as u64 {
+        return Err(Error::MaskTooLong);
+    }
+
+    let mut output: Vec<u8> = Vec::with_capacity(mask_len + h_len);
+    let mut counter: u32 = 0;
+    while output.len() < mask_len {
+        let mut hasher = D::new();
+        hasher.update(seed);
+        hasher.update(counter.to_be_bytes());
+        output.extend_from_slice(&hasher.finalize());
+        counter = counter.wrapping_add(1);
+    }
+    output.truncate(mask_len);
+
+    Ok(output)
+}
+
+#[cfg(test)]
+#[test]
+fn test_mgf1_length() {
+    use sha2::Sha256;
+
+    // lengths that are not a multiple of hLen must not lose the last block
+    for mask_len in [0, 1, 31, 32, 33, 64, 65, 223] {
+        let mask = mgf1::<Sha256>(b"seed", mask_len).unwrap();
+        assert_eq!(mask.len(), mask_len);
+    }
+    let long = mgf1::<Sha256>(b"seed", 65).unwrap();
+    assert_eq!(&long[..33], &mgf1::<Sha256>(b"seed", 33).unwrap()[..]);
+}
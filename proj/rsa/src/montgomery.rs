@@ -0,0 +1,188 @@
+// Constant-time modular exponentiation over fixed-width limbs
+//
+// `BigUint::modpow` trims leading zero limbs and skips work depending on the bits
+// of the exponent, so its running time leaks information about secret exponents.
+// Here every operand is padded to the width of the modulus and the exponent is
+// processed in fixed 4-bit windows, with the table entry picked by a full scan.
+use num::Zero;
+use num_bigint::BigUint;
+use subtle::{Choice, ConditionallySelectable, ConstantTimeEq};
+
+const WINDOW: usize = 4;
+
+// Montgomery arithmetic modulo an odd m, with R = 2^(64 * limbs)
+struct Montgomery {
+    m: Vec<u64>,
+    // -m^-1 mod 2^64
+    m_inv: u64,
+    // R^2 mod m, to convert into Montgomery form
+    r2: Vec<u64>,
+}
+
+impl Montgomery {
+    fn new(modulus: &BigUint) -> Self {
+        let m = modulus.to_u64_digits();
+        let limbs = m.len();
+
+        // Newton iteration doubles the number of correct low bits each round
+        let mut inv: u64 = 1;
+        for _ in 0..6 {
+            inv = inv.wrapping_mul(2_u64.wrapping_sub(m[0].wrapping_mul(inv)));
+        }
+
+        let r2 = (BigUint::from(1_u32) << (128 * limbs)) % modulus;
+        Montgomery {
+            m_inv: inv.wrapping_neg(),
+            r2: Self::to_limbs(&r2, limbs),
+            m,
+        }
+    }
+
+    fn to_limbs(x: &BigUint, limbs: usize) -> Vec<u64> {
+        let mut digits = x.to_u64_digits();
+        digits.resize(limbs, 0);
+        digits
+    }
+
+    // a * b * R^-1 mod m (CIOS), for a, b < m
+    fn mul(&self, a: &[u64], b: &[u64]) -> Vec<u64> {
+        let s = self.m.len();
+        let mut t = vec![0_u64; s + 2];
+
+        for &b_i in b {
+            let mut carry: u64 = 0;
+            for j in 0..s {
+                let v = t[j] as u128 + a[j] as u128 * b_i as u128 + carry as u128;
+                t[j] = v as u64;
+                carry = (v >> 64) as u64;
+            }
+            let v = t[s] as u128 + carry as u128;
+            t[s] = v as u64;
+            t[s + 1] = (v >> 64) as u64;
+
+            let u = t[0].wrapping_mul(self.m_inv);
+            let v = t[0] as u128 + u as u128 * self.m[0] as u128;
+            let mut carry = (v >> 64) as u64;
+            for j in 1..s {
+                let v = t[j] as u128 + u as u128 * self.m[j] as u128 + carry as u128;
+                t[j - 1] = v as u64;
+                carry = (v >> 64) as u64;
+            }
+            let v = t[s] as u128 + carry as u128;
+            t[s - 1] = v as u64;
+            t[s] = t[s + 1] + (v >> 64) as u64;
+        }
+
+        // t < 2m, so a single subtraction is enough; it is always computed and
+        // then selected, instead of branching on the borrow
+        let mut reduced = vec![0_u64; s];
+        let mut borrow: u64 = 0;
+        for j in 0..s {
+            let (d1, b1) = t[j].overflowing_sub(self.m[j]);
+            let (d2, b2) = d1.overflowing_sub(borrow);
+            reduced[j] = d2;
+            borrow = (b1 | b2) as u64;
+        }
+        let (_, b) = t[s].overflowing_sub(borrow);
+        let keep_t = Choice::from(b as u8);
+        for j in 0..s {
+            reduced[j] = u64::conditional_select(&reduced[j], &t[j], keep_t);
+        }
+        reduced
+    }
+
+    // table[index], touching every entry
+    fn select(table: &[Vec<u64>], index: u64) -> Vec<u64> {
+        let mut out = vec![0_u64; table[0].len()];
+        for (i, entry) in table.iter().enumerate() {
+            let hit = (i as u64).ct_eq(&index);
+            for (o, e) in out.iter_mut().zip(entry) {
+                o.conditional_assign(e, hit);
+            }
+        }
+        out
+    }
+}
+
+// base^exp mod modulus, in time that depends only on the sizes of modulus and exp
+pub fn ct_modpow(base: &BigUint, exp: &BigUint, modulus: &BigUint) -> BigUint {
+    // Montgomery reduction needs an odd modulus; RSA moduli and primes always are
+    if modulus.is_zero() || !modulus.bit(0) {
+        return base.modpow(exp, modulus);
+    }
+
+    let mont = Montgomery::new(modulus);
+    let limbs = mont.m.len();
+
+    // the base is reduced with variable-time division; callers blind it beforehand
+    let x = Montgomery::to_limbs(&(base % modulus), limbs);
+    let x = mont.mul(&x, &mont.r2);
+
+    // table[i] = x^i in Montgomery form
+    let mut one = vec![0_u64; limbs];
+    one[0] = 1;
+    let mut table = vec![mont.mul(&one, &mont.r2), x];
+    for i in 2..1 << WINDOW {
+        let next = mont.mul(&table[i - 1], &table[1]);
+        table.push(next);
+    }
+
+    let exp_limbs = exp.to_u64_digits().len().max(limbs);
+    let exp = Montgomery::to_limbs(exp, exp_limbs);
+
+    let mut acc = table[0].clone();
+    for window in (0..exp_limbs * 64 / WINDOW).rev() {
+        for _ in 0..WINDOW {
+            acc = mont.mul(&acc, &acc);
+        }
+        let bit = window * WINDOW;
+        let w = (exp[bit / 64] >> (bit % 64)) & ((1 << WINDOW) - 1);
+        acc = mont.mul(&acc, &Montgomery::select(&table, w));
+    }
+
+    // back from Montgomery form
+    let mut digits = mont.mul(&acc, &one);
+    while digits.last() == Some(&0) {
+        digits.pop();
+    }
+    let mut bytes = Vec::with_capacity(digits.len() * 8);
+    for d in digits.iter().rev() {
+        bytes.extend_from_slice(&d.to_be_bytes());
+    }
+    BigUint::from_bytes_be(&bytes)
+}
+
+#[cfg(test)]
+#[test]
+fn test_ct_modpow() {
+    use num_bigint::RandBigInt;
+
+    let mut rng = rand::thread_rng();
+    for bits in [64, 65, 127, 128, 512, 1031] {
+        let modulus = rng.gen_biguint(bits) | BigUint::from(1_u32);
+        let base = rng.gen_biguint(bits + 7);
+        let exp = rng.gen_biguint(bits);
+        assert_eq!(
+            ct_modpow(&base, &exp, &modulus),
+            base.modpow(&exp, &modulus)
+        );
+    }
+
+    let modulus = BigUint::from(0xffff_fffb_u64);
+    assert_eq!(
+        ct_modpow(&BigUint::from(7_u32), &BigUint::zero(), &modulus),
+        BigUint::from(1_u32)
+    );
+    assert_eq!(
+        ct_modpow(&BigUint::zero(), &BigUint::from(5_u32), &modulus),
+        BigUint::zero()
+    );
+    assert_eq!(
+        ct_modpow(
+            &BigUint::from(3_u32),
+            &BigUint::from(10_u32),
+            &BigUint::from(1000_u32)
+        ),
+        BigUint::from(49_u32)
+    );
+}
@@ -0,0 +1,163 @@
+// RSAES-OAEP (RFC 8017 7.1)
+use rand::{CryptoRng, RngCore};
+use sha2::{Digest, Sha256};
+use subtle::{ConditionallySelectable, ConstantTimeEq};
+
+use crate::{
+    convert::{i2osp, os2ip, string_xor},
+    errors::{Error, Result},
+    mgf::mgf1,
+    plain_rsa::{PrivateKey, PublicKey},
+};
+
+pub fn oaep_encrypt(pk: &PublicKey, msg: &[u8]) -> Result<Vec<u8>> {
+    oaep_encrypt_with_label(pk, msg, &[])
+}
+
+pub fn oaep_encrypt_with_label(pk: &PublicKey, msg: &[u8], label: &[u8]) -> Result<Vec<u8>> {
+    let mut rng = rand::thread_rng();
+    oaep_encrypt_with_rng(&mut rng, pk, msg, label)
+}
+
+pub fn oaep_encrypt_with_rng<R: RngCore + CryptoRng>(
+    rng: &mut R,
+    pk: &PublicKey,
+    msg: &[u8],
+    label: &[u8],
+) -> Result<Vec<u8>> {
+    oaep_encrypt_with_digest_and_rng::<Sha256, Sha256, R>(rng, pk, msg, label)
+}
+
+// D hashes the label, MD is the hash underlying MGF1; RFC 8017 allows them to differ
+pub fn oaep_encrypt_with_digest<D: Digest, MD: Digest>(
+    pk: &PublicKey,
+    msg: &[u8],
+    label: &[u8],
+) -> Result<Vec<u8>> {
+    let mut rng = rand::thread_rng();
+    oaep_encrypt_with_digest_and_rng::<D, MD, _>(&mut rng, pk, msg, label)
+}
+
+pub fn oaep_encrypt_with_digest_and_rng<D: Digest, MD: Digest, R: RngCore + CryptoRng>(
+    rng: &mut R,
+    pk: &PublicKey,
+    msg: &[u8],
+    label: &[u8],
+) -> Result<Vec<u8>> {
+    let h_len = <D as Digest>::output_size();
+    let k = pk.size();
+
+    // check length
+    if k < 2 * h_len + 2 || msg.len() > k - 2 * h_len - 2 {
+        return Err(Error::MessageTooLong);
+    }
+
+    // EME-OAEP encoding
+    let label_hash = D::digest(label);
+    let ps = vec![0_u8; k - msg.len() - 2 * h_len - 2];
+    let db = [&label_hash[..], &ps[..], &[0x01], msg].concat();
+
+    let mut seed = vec![0_u8; h_len];
+    rng.fill_bytes(&mut seed);
+
+    let db_mask = mgf1::<MD>(&seed, k - h_len - 1)?;
+    let masked_db = string_xor(&db, &db_mask)?;
+
+    let seed_mask = mgf1::<MD>(&masked_db, h_len)?;
+    let masked_seed = string_xor(&seed, &seed_mask)?;
+
+    let em = [&[0x00_u8][..], &masked_seed[..], &masked_db[..]].concat();
+
+    // RSA encryption
+    let m = os2ip(&em)?;
+    let c = pk.rsaep(&m)?;
+    i2osp(&c, k)
+}
+
+pub fn oaep_decrypt(sk: &PrivateKey, ciphertext: &[u8]) -> Result<Vec<u8>> {
+    oaep_decrypt_with_label(sk, ciphertext, &[])
+}
+
+pub fn oaep_decrypt_with_label(
+    sk: &PrivateKey,
+    ciphertext: &[u8],
+    label: &[u8],
+) -> Result<Vec<u8>> {
+    let mut rng = rand::thread_rng();
+    oaep_decrypt_with_rng(&mut rng, sk, ciphertext, label)
+}
+
+// `rng` blinds the RSA decryption
+pub fn oaep_decrypt_with_rng<R: RngCore + CryptoRng>(
+    rng: &mut R,
+    sk: &PrivateKey,
+    ciphertext: &[u8],
+    label: &[u8],
+) -> Result<Vec<u8>> {
+    oaep_decrypt_with_digest_and_rng::<Sha256, Sha256, R>(rng, sk, ciphertext, label)
+}
+
+pub fn oaep_decrypt_with_digest<D: Digest, MD: Digest>(
+    sk: &PrivateKey,
+    ciphertext: &[u8],
+    label: &[u8],
+) -> Result<Vec<u8>> {
+    let mut rng = rand::thread_rng();
+    oaep_decrypt_with_digest_and_rng::<D, MD, _>(&mut rng, sk, ciphertext, label)
+}
+
+pub fn oaep_decrypt_with_digest_and_rng<D: Digest, MD: Digest, R: RngCore + CryptoRng>(
+    rng: &mut R,
+    sk: &PrivateKey,
+    ciphertext: &[u8],
+    label: &[u8],
+) -> Result<Vec<u8>> {
+    let h_len = <D as Digest>::output_size();
+    let k = sk.size();
+
+    // check length
+    if k != ciphertext.len() || k < 2 * h_len + 2 {
+        return Err(Error::DecryptionError);
+    }
+
+    // RSA decryption
+    let c = os2ip(ciphertext)?;
+    let m = sk
+        .rsadp_with_rng(rng, &c)
+        .map_err(|_| Error::DecryptionError)?;
+    let em = i2osp(&m, k)?;
+
+    // EME-OAEP decoding
+    let label_hash = D::digest(label);
+    let y = em[0];
+    let (masked_seed, masked_db) = em[1..].split_at(h_len);
+
+    let seed_mask = mgf1::<MD>(masked_db, h_len)?;
+    let seed = string_xor(masked_seed, &seed_mask)?;
+
+    let db_mask = mgf1::<MD>(&seed, k - h_len - 1)?;
+    let db = string_xor(masked_db, &db_mask)?;
+
+    // All checks below run in constant time, so that the caller cannot tell
+    // which one failed (Manger's attack)
+    let mut good = y.ct_eq(&0) & db[..h_len].ct_eq(&label_hash[..]);
+
+    // DB = lHash || PS || 0x01 || M, where PS is a run of zero octets
+    let mut looking_for_index = 1_u8.ct_eq(&1);
+    let mut invalid = 0_u8.ct_eq(&1);
+    let mut index: u32 = 0;
+    for (i, b) in db[h_len..].iter().enumerate() {
+        let is_zero = b.ct_eq(&0);
+        let is_one = b.ct_eq(&1);
+        index.conditional_assign(&(i as u32), looking_for_index & is_one);
+        looking_for_index &= !is_one;
+        invalid |= looking_for_index & !is_zero;
+    }
+    good &= !invalid & !looking_for_index;
+
+    if !bool::from(good) {
+        return Err(Error::DecryptionError);
+    }
+
+    Ok(db[h_len + index as usize + 1..].to_vec())
+}
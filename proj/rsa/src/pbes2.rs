@@ -0,0 +1,340 @@
+// PBES2 password-based encryption (RFC 8018 6.2) with AES-256-CBC, keyed by
+// PBKDF2-HMAC-SHA256 or scrypt (RFC 7914)
+use aes::cipher::{block_padding::Pkcs7, BlockDecryptMut, BlockEncryptMut, KeyIvInit};
+use rand::{CryptoRng, RngCore};
+use sha2::Sha256;
+use zeroize::Zeroizing;
+
+use crate::{
+    der::{self, Reader, OID_AES_256_CBC, OID_HMAC_WITH_SHA256, OID_PBKDF2, OID_SCRYPT},
+    errors::{Error, Result},
+};
+
+const KEY_LEN: usize = 32;
+
+// Iteration count recommended by OWASP for PBKDF2-HMAC-SHA256
+pub const PBKDF2_DEFAULT_ITERATIONS: u32 = 600_000;
+
+// Cost caps for reading and writing: far above any sane setting, well below a
+// key derivation that runs for hours or an allocation that aborts
+const MAX_PBKDF2_ITERATIONS: u32 = 10_000_000;
+const MAX_SCRYPT_LOG_N: u8 = 20;
+const MAX_SCRYPT_R_P: u64 = 32;
+// scrypt uses 128 * r * N octets
+const MAX_SCRYPT_MEMORY: u64 = 1 << 30;
+
+fn pbkdf2_within_caps(iterations: u32) -> bool {
+    (1..=MAX_PBKDF2_ITERATIONS).contains(&iterations)
+}
+
+fn scrypt_within_caps(log_n: u8, r: u32, p: u32) -> bool {
+    (1..=MAX_SCRYPT_LOG_N).contains(&log_n)
+        && r != 0
+        && p != 0
+        && r as u64 * p as u64 <= MAX_SCRYPT_R_P
+        && (128 * r as u64) << log_n <= MAX_SCRYPT_MEMORY
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Kdf {
+    Pbkdf2 {
+        salt: Vec<u8>,
+        iterations: u32,
+    },
+    // the cost parameter is N = 2^log_n
+    Scrypt {
+        salt: Vec<u8>,
+        log_n: u8,
+        r: u32,
+        p: u32,
+    },
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Parameters {
+    pub kdf: Kdf,
+    pub iv: [u8; 16],
+}
+
+impl Parameters {
+    // Costs that `decode` would reject are refused, so that every key written can
+    // be read back
+    pub fn pbkdf2_sha256_aes256cbc(iterations: u32, salt: &[u8], iv: [u8; 16]) -> Result<Self> {
+        if !pbkdf2_within_caps(iterations) {
+            return Err(Error::EncodingError);
+        }
+        Ok(Parameters {
+            kdf: Kdf::Pbkdf2 {
+                salt: salt.to_vec(),
+                iterations,
+            },
+            iv,
+        })
+    }
+
+    // Same caps as `pbkdf2_sha256_aes256cbc`, which also keep N = 2^log_n within
+    // 64 bits for the encoding
+    pub fn scrypt_aes256cbc(log_n: u8, r: u32, p: u32, salt: &[u8], iv: [u8; 16]) -> Result<Self> {
+        if !scrypt_within_caps(log_n, r, p) {
+            return Err(Error::EncodingError);
+        }
+        scrypt::Params::new(log_n, r, p, KEY_LEN).map_err(|_| Error::EncodingError)?;
+        Ok(Parameters {
+            kdf: Kdf::Scrypt {
+                salt: salt.to_vec(),
+                log_n,
+                r,
+                p,
+            },
+            iv,
+        })
+    }
+
+    // PBKDF2 with the default iteration count and a fresh 16-octet salt and IV
+    pub fn generate<R: RngCore + CryptoRng>(rng: &mut R) -> Self {
+        let mut salt = [0_u8; 16];
+        let mut iv = [0_u8; 16];
+        rng.fill_bytes(&mut salt);
+        rng.fill_bytes(&mut iv);
+        Parameters {
+            kdf: Kdf::Pbkdf2 {
+                salt: salt.to_vec(),
+                iterations: PBKDF2_DEFAULT_ITERATIONS,
+            },
+            iv,
+        }
+    }
+
+    fn derive_key(&self, password: &[u8]) -> Result<Zeroizing<[u8; KEY_LEN]>> {
+        let mut key = Zeroizing::new([0_u8; KEY_LEN]);
+        match &self.kdf {
+            Kdf::Pbkdf2 { salt, iterations } => {
+                pbkdf2::pbkdf2_hmac::<Sha256>(password, salt, *iterations, &mut key[..]);
+            }
+            Kdf::Scrypt { salt, log_n, r, p } => {
+                let params = scrypt::Params::new(*log_n, *r, *p, KEY_LEN)
+                    .map_err(|_| Error::EncodingError)?;
+                scrypt::scrypt(password, salt, &params, &mut key[..])
+                    .map_err(|_| Error::EncodingError)?;
+            }
+        }
+        Ok(key)
+    }
+
+    pub fn encrypt(&self, password: &[u8], plaintext: &[u8]) -> Result<Vec<u8>> {
+        let key = self.derive_key(password)?;
+        Ok(
+            cbc::Encryptor::<aes::Aes256>::new(key[..].into(), &self.iv.into())
+                .encrypt_padded_vec_mut::<Pkcs7>(plaintext),
+        )
+    }
+
+    // A bad padding is what a wrong password looks like to CBC
+    pub fn decrypt(&self, password: &[u8], ciphertext: &[u8]) -> Result<Vec<u8>> {
+        let key = self.derive_key(password)?;
+        cbc::Decryptor::<aes::Aes256>::new(key[..].into(), &self.iv.into())
+            .decrypt_padded_vec_mut::<Pkcs7>(ciphertext)
+            .map_err(|_| Error::WrongPassword)
+    }
+
+    // PBES2-params ::= SEQUENCE { keyDerivationFunc, encryptionScheme }
+    pub fn encode(&self) -> Vec<u8> {
+        let kdf = match &self.kdf {
+            Kdf::Pbkdf2 { salt, iterations } => der::sequence(&[
+                der::object_identifier(OID_PBKDF2),
+                der::sequence(&[
+                    der::octet_string(salt),
+                    der::integer(&(*iterations).into()),
+                    der::sequence(&[der::object_identifier(OID_HMAC_WITH_SHA256), der::null()]),
+                ]),
+            ]),
+            Kdf::Scrypt { salt, log_n, r, p } => der::sequence(&[
+                der::object_identifier(OID_SCRYPT),
+                der::sequence(&[
+                    der::octet_string(salt),
+                    der::integer(&(1_u64 << log_n).into()),
+                    der::integer(&(*r).into()),
+                    der::integer(&(*p).into()),
+                ]),
+            ]),
+        };
+        let scheme = der::sequence(&[
+            der::object_identifier(OID_AES_256_CBC),
+            der::octet_string(&self.iv),
+        ]);
+        der::sequence(&[kdf, scheme])
+    }
+
+    pub fn decode(reader: &mut Reader) -> Result<Self> {
+        let mut seq = reader.read_sequence()?;
+
+        let mut kdf_id = seq.read_sequence()?;
+        let kdf = match kdf_id.read_object_identifier()? {
+            OID_PBKDF2 => {
+                let mut params = kdf_id.read_sequence()?;
+                let salt = params.read_octet_string()?.to_vec();
+                let iterations = u32::try_from(params.read_small_integer()?)
+                    .map_err(|_| Error::MalformedEncoding)?;
+                if !pbkdf2_within_caps(iterations) {
+                    return Err(Error::MalformedEncoding);
+                }
+                if params.peek_tag() == Some(der::INTEGER)
+                    && params.read_small_integer()? != KEY_LEN as u64
+                {
+                    return Err(Error::MalformedEncoding);
+                }
+                // the PRF defaults to HMAC-SHA1, which is not supported
+                if params.is_empty() {
+                    return Err(Error::UnsupportedAlgorithm);
+                }
+                let mut prf = params.read_sequence()?;
+                if prf.read_object_identifier()? != OID_HMAC_WITH_SHA256 {
+                    return Err(Error::UnsupportedAlgorithm);
+                }
+                if !prf.is_empty() {
+                    prf.read_null()?;
+                }
+                prf.finish()?;
+                params.finish()?;
+                Kdf::Pbkdf2 { salt, iterations }
+            }
+            OID_SCRYPT => {
+                let mut params = kdf_id.read_sequence()?;
+                let salt = params.read_octet_string()?.to_vec();
+                let n = params.read_small_integer()?;
+                if !n.is_power_of_two() || n < 2 {
+                    return Err(Error::MalformedEncoding);
+                }
+                let r = u32::try_from(params.read_small_integer()?)
+                    .map_err(|_| Error::MalformedEncoding)?;
+                let p = u32::try_from(params.read_small_integer()?)
+                    .map_err(|_| Error::MalformedEncoding)?;
+                if !params.is_empty() && params.read_small_integer()? != KEY_LEN as u64 {
+                    return Err(Error::MalformedEncoding);
+                }
+                params.finish()?;
+                let log_n = n.trailing_zeros() as u8;
+                if !scrypt_within_caps(log_n, r, p) {
+                    return Err(Error::MalformedEncoding);
+                }
+                Kdf::Scrypt { salt, log_n, r, p }
+            }
+            _ => return Err(Error::UnsupportedAlgorithm),
+        };
+        kdf_id.finish()?;
+
+        let mut scheme = seq.read_sequence()?;
+        if scheme.read_object_identifier()? != OID_AES_256_CBC {
+            return Err(Error::UnsupportedAlgorithm);
+        }
+        let iv: [u8; 16] = scheme
+            .read_octet_string()?
+            .try_into()
+            .map_err(|_| Error::MalformedEncoding)?;
+        scheme.finish()?;
+        seq.finish()?;
+
+        Ok(Parameters { kdf, iv })
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn test_pbes2_round_trip() {
+    let params = Parameters::scrypt_aes256cbc(4, 8, 1, b"saltsalt", [7; 16]).unwrap();
+    let ciphertext = params.encrypt(b"password", b"secret key").unwrap();
+    assert_eq!(ciphertext.len(), 16);
+    assert_eq!(
+        params.decrypt(b"password", &ciphertext).unwrap(),
+        b"secret key"
+    );
+
+    let encoded = params.encode();
+    assert_eq!(Parameters::decode(&mut Reader::new(&encoded)), Ok(params));
+
+    // costs above the caps cannot be written, N = 2^64 and beyond included
+    for (log_n, r, p) in [
+        (0, 8, 1),
+        (21, 8, 1),
+        (64, 8, 1),
+        (255, 8, 1),
+        (4, 64, 1),
+        (4, 8, 0),
+    ] {
+        assert_eq!(
+            Parameters::scrypt_aes256cbc(log_n, r, p, b"saltsalt", [7; 16]),
+            Err(Error::EncodingError)
+        );
+    }
+    for iterations in [0, u32::MAX] {
+        assert_eq!(
+            Parameters::pbkdf2_sha256_aes256cbc(iterations, b"saltsalt", [7; 16]),
+            Err(Error::EncodingError)
+        );
+    }
+
+    // nor read, before any key derivation
+    let scheme = der::sequence(&[
+        der::object_identifier(OID_AES_256_CBC),
+        der::octet_string(&[7; 16]),
+    ]);
+    let pbes2 = |kdf_oid: &[u8], kdf_params: &[Vec<u8>]| {
+        let kdf = der::sequence(&[der::object_identifier(kdf_oid), der::sequence(kdf_params)]);
+        der::sequence(&[kdf, scheme.clone()])
+    };
+    let scrypt = |n: u64, r: u32| {
+        pbes2(
+            OID_SCRYPT,
+            &[
+                der::octet_string(b"saltsalt"),
+                der::integer(&n.into()),
+                der::integer(&r.into()),
+                der::integer(&1_u32.into()),
+            ],
+        )
+    };
+    let pbkdf2 = |iterations: u32| {
+        pbes2(
+            OID_PBKDF2,
+            &[
+                der::octet_string(b"saltsalt"),
+                der::integer(&iterations.into()),
+                der::sequence(&[der::object_identifier(OID_HMAC_WITH_SHA256), der::null()]),
+            ],
+        )
+    };
+    for encoded in [
+        scrypt(1 << 50, 8),
+        scrypt(16, 64),
+        pbkdf2(u32::MAX),
+        pbkdf2(0),
+    ] {
+        assert_eq!(
+            Parameters::decode(&mut Reader::new(&encoded)),
+            Err(Error::MalformedEncoding)
+        );
+    }
+    assert!(Parameters::decode(&mut Reader::new(&scrypt(16, 8))).is_ok());
+    assert!(Parameters::decode(&mut Reader::new(&pbkdf2(1000))).is_ok());
+
+    // well-formed, but with algorithms other than the supported ones
+    use der::{OID_AES_128_CBC, OID_HMAC_WITH_SHA1};
+    let salt = der::octet_string(b"saltsalt");
+    let iterations = der::integer(&1000_u32.into());
+    let sha1 = der::sequence(&[der::object_identifier(OID_HMAC_WITH_SHA1), der::null()]);
+    // the cipher OID comes right before the IV, and has the same length
+    let mut aes128 = pbkdf2(1000);
+    let at = aes128.len() - 2 - 16 - OID_AES_128_CBC.len();
+    aes128[at..at + OID_AES_128_CBC.len()].copy_from_slice(OID_AES_128_CBC);
+    for encoded in [
+        pbes2(OID_PBKDF2, &[salt.clone(), iterations.clone(), sha1]),
+        pbes2(OID_PBKDF2, &[salt.clone(), iterations.clone()]),
+        pbes2(OID_HMAC_WITH_SHA256, &[salt, iterations]),
+        aes128,
+    ] {
+        assert_eq!(
+            Parameters::decode(&mut Reader::new(&encoded)),
+            Err(Error::UnsupportedAlgorithm)
+        );
+    }
+}
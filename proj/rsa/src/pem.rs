@@ -0,0 +1,74 @@
+// PEM armor (RFC 7468) around DER documents
+use base64::{engine::general_purpose, Engine};
+
+use crate::errors::{Error, Result};
+
+// Base64 body wrapped at 64 columns, as written by OpenSSL
+pub fn encode(label: &str, der: &[u8]) -> String {
+    encode_wrapped(label, der, 64)
+}
+
+// OpenSSH wraps its private keys at 70 columns instead
+pub fn encode_wrapped(label: &str, der: &[u8], width: usize) -> String {
+    let body = general_purpose::STANDARD.encode(der);
+
+    let mut pem = format!("-----BEGIN {}-----\n", label);
+    for line in body.as_bytes().chunks(width) {
+        pem.push_str(std::str::from_utf8(line).unwrap());
+        pem.push('\n');
+    }
+    pem.push_str(&format!("-----END {}-----\n", label));
+    pem
+}
+
+// Label and DER document of the first PEM block in `text`; a block cut off
+// before its END line is reported as a truncated file
+pub fn decode(text: &str) -> Result<(String, Vec<u8>)> {
+    let mut lines = text.lines().map(str::trim).skip_while(|l| l.is_empty());
+
+    let label = lines
+        .next()
+        .ok_or(Error::TruncatedFile)?
+        .strip_prefix("-----BEGIN ")
+        .and_then(|l| l.strip_suffix("-----"))
+        .ok_or(Error::MalformedEncoding)?;
+    let end = format!("-----END {}-----", label);
+
+    let mut body = String::new();
+    for line in lines {
+        if line == end {
+            let der = general_purpose::STANDARD
+                .decode(&body)
+                .map_err(|_| Error::MalformedEncoding)?;
+            return Ok((label.to_owned(), der));
+        }
+        // encapsulated headers (e.g. the legacy Proc-Type encryption) are not supported
+        if line.contains(':') {
+            return Err(Error::MalformedEncoding);
+        }
+        body.push_str(line);
+    }
+    Err(Error::TruncatedFile)
+}
+
+// DER document of a PEM block that must carry `label`
+pub fn decode_label(text: &str, label: &str) -> Result<Vec<u8>> {
+    let (found, der) = decode(text)?;
+    if found != label {
+        return Err(Error::WrongKeyType);
+    }
+    Ok(der)
+}
+
+#[cfg(test)]
+#[test]
+fn test_pem_round_trip() {
+    let der: Vec<u8> = (0..=255).collect();
+    let pem = encode("TEST", &der);
+    assert!(pem.lines().all(|l| l.len() <= 64));
+    assert_eq!(decode(&pem), Ok(("TEST".to_owned(), der.clone())));
+    assert_eq!(decode_label(&pem, "OTHER"), Err(Error::WrongKeyType));
+    assert_eq!(decode(&pem[..pem.len() - 10]), Err(Error::TruncatedFile));
+    assert_eq!(decode(""), Err(Error::TruncatedFile));
+    assert_eq!(decode("-----BEGIN"), Err(Error::MalformedEncoding));
+}
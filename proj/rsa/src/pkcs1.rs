@@ -0,0 +1,152 @@
+// PKCS #1 key syntax (RFC 8017 A.1) in DER and PEM, as used by `openssl rsa`
+use num::Zero;
+use num_bigint::BigUint;
+use zeroize::Zeroizing;
+
+use crate::{
+    der::{self, Reader},
+    errors::{Error, Result},
+    pem,
+    plain_rsa::{PrivateKey, PublicKey},
+    secret::Secret,
+};
+
+pub const PUBLIC_KEY_LABEL: &str = "RSA PUBLIC KEY";
+pub const PRIVATE_KEY_LABEL: &str = "RSA PRIVATE KEY";
+
+impl PublicKey {
+    // RSAPublicKey ::= SEQUENCE { modulus INTEGER, publicExponent INTEGER }
+    pub fn to_pkcs1_der(&self) -> Vec<u8> {
+        der::sequence(&[der::integer(&self.n), der::integer(&self.e)])
+    }
+
+    pub fn from_pkcs1_der(bytes: &[u8]) -> Result<Self> {
+        let mut outer = Reader::new(bytes);
+        let mut seq = outer.read_sequence()?;
+        let n = seq.read_integer()?;
+        let e = seq.read_integer()?;
+        seq.finish()?;
+        outer.finish()?;
+
+        if n.is_zero() || e.is_zero() {
+            return Err(Error::MalformedEncoding);
+        }
+        PublicKey::new(&n, &e)
+    }
+
+    pub fn to_pkcs1_pem(&self) -> String {
+        pem::encode(PUBLIC_KEY_LABEL, &self.to_pkcs1_der())
+    }
+
+    pub fn from_pkcs1_pem(text: &str) -> Result<Self> {
+        PublicKey::from_pkcs1_der(&pem::decode_label(text, PUBLIC_KEY_LABEL)?)
+    }
+}
+
+impl PrivateKey {
+    // RSAPrivateKey ::= SEQUENCE { version, n, e, d, p, q, dp, dq, qinv,
+    //     otherPrimeInfos OPTIONAL }
+    // version is 1 (multi) when otherPrimeInfos is present, and 0 (two-prime)
+    // otherwise
+    pub fn to_pkcs1_der(&self) -> Vec<u8> {
+        let version = if self.other_primes.is_empty() {
+            0_u32
+        } else {
+            1
+        };
+        // the encoded fields hold the private integers, and are wiped once joined
+        let mut fields = Zeroizing::new(vec![
+            der::integer(&version.into()),
+            der::integer(&self.n),
+            der::integer(&self.e),
+            der::integer(&self.d),
+            der::integer(&self.p),
+            der::integer(&self.q),
+            der::integer(&self.dp),
+            der::integer(&self.dq),
+            der::integer(&self.qinv),
+        ]);
+        // OtherPrimeInfo ::= SEQUENCE { prime, exponent, coefficient }
+        if !self.other_primes.is_empty() {
+            let infos: Zeroizing<Vec<Vec<u8>>> = Zeroizing::new(
+                self.other_primes
+                    .iter()
+                    .map(|info| {
+                        der::sequence(&Zeroizing::new(vec![
+                            der::integer(&info.prime),
+                            der::integer(&info.exponent),
+                            der::integer(&info.coefficient),
+                        ]))
+                    })
+                    .collect(),
+            );
+            fields.push(der::sequence(&infos));
+        }
+        der::sequence(&fields)
+    }
+
+    // The CRT fields are checked against the ones derived from n, e, d and the
+    // primes
+    pub fn from_pkcs1_der(bytes: &[u8]) -> Result<Self> {
+        let mut outer = Reader::new(bytes);
+        let mut seq = outer.read_sequence()?;
+        let version = seq.read_small_integer()?;
+        if version > 1 {
+            return Err(Error::MalformedEncoding);
+        }
+        let n = seq.read_integer()?;
+        let e = seq.read_integer()?;
+        let d = Secret::new(seq.read_integer()?);
+        let p = Secret::new(seq.read_integer()?);
+        let q = Secret::new(seq.read_integer()?);
+        let dp = Secret::new(seq.read_integer()?);
+        let dq = Secret::new(seq.read_integer()?);
+        let qinv = Secret::new(seq.read_integer()?);
+
+        // otherPrimeInfos is present, with at least one prime, iff version is 1
+        let mut primes = vec![p, q];
+        let mut crt = vec![];
+        if version == 1 {
+            let mut infos = seq.read_sequence()?;
+            if infos.is_empty() {
+                return Err(Error::MalformedEncoding);
+            }
+            while !infos.is_empty() {
+                let mut info = infos.read_sequence()?;
+                primes.push(Secret::new(info.read_integer()?));
+                crt.push((
+                    Secret::new(info.read_integer()?),
+                    Secret::new(info.read_integer()?),
+                ));
+                info.finish()?;
+            }
+        }
+        seq.finish()?;
+        outer.finish()?;
+
+        let primes: Vec<&BigUint> = primes.iter().map(|r| &**r).collect();
+        let sk = PrivateKey::from_primes(&n, &e, &d, &primes)?;
+        if sk.dp != dp
+            || sk.dq != dq
+            || sk.qinv != qinv
+            || sk
+                .other_primes
+                .iter()
+                .zip(&crt)
+                .any(|(info, (exponent, coefficient))| {
+                    info.exponent != *exponent || info.coefficient != *coefficient
+                })
+        {
+            return Err(Error::InvalidKeyPair("inconsistent CRT parameters"));
+        }
+        Ok(sk)
+    }
+
+    pub fn to_pkcs1_pem(&self) -> String {
+        pem::encode(PRIVATE_KEY_LABEL, &Zeroizing::new(self.to_pkcs1_der()))
+    }
+
+    pub fn from_pkcs1_pem(text: &str) -> Result<Self> {
+        PrivateKey::from_pkcs1_der(&pem::decode_label(text, PRIVATE_KEY_LABEL)?)
+    }
+}
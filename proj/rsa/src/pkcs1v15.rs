@@ -0,0 +1,241 @@
+// PKCS #1 v1.5 paddings (RFC 8017 7.2, 8.2 and 9.2)
+use hmac::{Hmac, Mac};
+use rand::{CryptoRng, RngCore};
+use sha1::Sha1;
+use sha2::{Digest, Sha224, Sha256, Sha384, Sha512};
+use subtle::{ConditionallySelectable, ConstantTimeEq, ConstantTimeGreater, ConstantTimeLess};
+use zeroize::Zeroizing;
+
+use crate::{
+    convert::{i2osp, os2ip},
+    errors::{Error, Result},
+    plain_rsa::{PrivateKey, PublicKey},
+};
+
+// DER encoding of the DigestInfo header for a hash function (RFC 8017 9.2, note 1),
+// i.e. everything in front of the digest value
+pub trait DigestInfo: Digest {
+    const PREFIX: &'static [u8];
+}
+
+impl DigestInfo for Sha1 {
+    const PREFIX: &'static [u8] = &[
+        0x30, 0x21, 0x30, 0x09, 0x06, 0x05, 0x2b, 0x0e, 0x03, 0x02, 0x1a, 0x05, 0x00, 0x04, 0x14,
+    ];
+}
+
+impl DigestInfo for Sha224 {
+    const PREFIX: &'static [u8] = &[
+        0x30, 0x2d, 0x30, 0x0d, 0x06, 0x09, 0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x04,
+        0x05, 0x00, 0x04, 0x1c,
+    ];
+}
+
+impl DigestInfo for Sha256 {
+    const PREFIX: &'static [u8] = &[
+        0x30, 0x31, 0x30, 0x0d, 0x06, 0x09, 0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x01,
+        0x05, 0x00, 0x04, 0x20,
+    ];
+}
+
+impl DigestInfo for Sha384 {
+    const PREFIX: &'static [u8] = &[
+        0x30, 0x41, 0x30, 0x0d, 0x06, 0x09, 0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x02,
+        0x05, 0x00, 0x04, 0x30,
+    ];
+}
+
+impl DigestInfo for Sha512 {
+    const PREFIX: &'static [u8] = &[
+        0x30, 0x51, 0x30, 0x0d, 0x06, 0x09, 0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x03,
+        0x05, 0x00, 0x04, 0x40,
+    ];
+}
+
+// EMSA-PKCS1-v1_5-ENCODE (RFC 8017 9.2)
+// EM = 0x00 || 0x01 || PS || 0x00 || T, where PS is at least 8 octets of 0xff
+fn emsa_pkcs1v15_encode<D: DigestInfo>(msg: &[u8], em_len: usize) -> Result<Vec<u8>> {
+    let t = [D::PREFIX, &D::digest(msg)[..]].concat();
+    if em_len < t.len() + 11 {
+        return Err(Error::EncodingError);
+    }
+
+    let ps = vec![0xff_u8; em_len - t.len() - 3];
+    Ok([&[0x00, 0x01], &ps[..], &[0x00], &t[..]].concat())
+}
+
+impl PrivateKey {
+    // RSASSA-PKCS1-V1_5-SIGN (RFC 8017 8.2.1)
+    pub fn sign_pkcs1v15<D: DigestInfo>(&self, msg: &[u8]) -> Result<Vec<u8>> {
+        let mut rng = rand::thread_rng();
+        self.sign_pkcs1v15_with_rng::<D, _>(&mut rng, msg)
+    }
+
+    // `rng` only blinds RSASP1, the signature itself is deterministic
+    pub fn sign_pkcs1v15_with_rng<D: DigestInfo, R: RngCore + CryptoRng>(
+        &self,
+        rng: &mut R,
+        msg: &[u8],
+    ) -> Result<Vec<u8>> {
+        let k = self.size();
+        let em = emsa_pkcs1v15_encode::<D>(msg, k)?;
+
+        let m = os2ip(&em)?;
+        let s = self.rsasp1_with_rng(rng, &m)?;
+        i2osp(&s, k)
+    }
+}
+
+impl PublicKey {
+    // RSASSA-PKCS1-V1_5-VERIFY (RFC 8017 8.2.2)
+    // The expected encoding is rebuilt and compared as a whole instead of parsing
+    // the recovered one, which rules out Bleichenbacher'06 forgeries for small e
+    pub fn verify_pkcs1v15<D: DigestInfo>(&self, msg: &[u8], signature: &[u8]) -> Result<()> {
+        let k = self.size();
+        if signature.len() != k {
+            return Err(Error::VerificationError);
+        }
+
+        let s = os2ip(signature)?;
+        let m = self.rsavp1(&s).map_err(|_| Error::VerificationError)?;
+        let em = i2osp(&m, k).map_err(|_| Error::VerificationError)?;
+
+        let expected = emsa_pkcs1v15_encode::<D>(msg, k)?;
+        if bool::from(em.ct_eq(&expected)) {
+            Ok(())
+        } else {
+            Err(Error::VerificationError)
+        }
+    }
+}
+
+// Key derivation key for implicit rejection: HMAC-SHA256 over the ciphertext,
+// keyed with SHA256(I2OSP(d, k)), as in OpenSSL 3.2+
+fn implicit_rejection_kdk(sk: &PrivateKey, ciphertext: &[u8]) -> Result<Zeroizing<Vec<u8>>> {
+    let d_bytes = Zeroizing::new(i2osp(&sk.d, sk.size())?);
+    let d_hash: Zeroizing<[u8; 32]> = Zeroizing::new(Sha256::digest(&*d_bytes).into());
+    let mut hmac =
+        Hmac::<Sha256>::new_from_slice(&*d_hash).map_err(|_| Error::InvalidBufferSize)?;
+    hmac.update(ciphertext);
+    Ok(Zeroizing::new(hmac.finalize().into_bytes().to_vec()))
+}
+
+// PRF used to derive the synthetic message and its length from the KDK
+fn implicit_rejection_prf(kdk: &[u8], label: &[u8], out_len: usize) -> Result<Vec<u8>> {
+    let bit_len = (out_len * 8) as u16;
+    let mut output: Vec<u8> = Vec::with_capacity(out_len + 32);
+    let mut iter: u16 = 0;
+    while output.len() < out_len {
+        let mut hmac = Hmac::<Sha256>::new_from_slice(kdk).map_err(|_| Error::InvalidBufferSize)?;
+        hmac.update(&iter.to_be_bytes());
+        hmac.update(label);
+        hmac.update(&bit_len.to_be_bytes());
+        output.extend_from_slice(&hmac.finalize().into_bytes());
+        iter += 1;
+    }
+    output.truncate(out_len);
+    Ok(output)
+}
+
+impl PublicKey {
+    // RSAES-PKCS1-V1_5-ENCRYPT (RFC 8017 7.2.1)
+    pub fn encrypt_pkcs1v15(&self, msg: &[u8]) -> Result<Vec<u8>> {
+        let mut rng = rand::thread_rng();
+        self.encrypt_pkcs1v15_with_rng(&mut rng, msg)
+    }
+
+    pub fn encrypt_pkcs1v15_with_rng<R: RngCore + CryptoRng>(
+        &self,
+        rng: &mut R,
+        msg: &[u8],
+    ) -> Result<Vec<u8>> {
+        let k = self.size();
+        if k < 11 || msg.len() > k - 11 {
+            return Err(Error::MessageTooLong);
+        }
+
+        // EM = 0x00 || 0x02 || PS || 0x00 || M, where PS is nonzero random octets
+        let mut ps = vec![0_u8; k - msg.len() - 3];
+        rng.fill_bytes(&mut ps);
+        for b in ps.iter_mut() {
+            while *b == 0 {
+                let mut byte = [0_u8; 1];
+                rng.fill_bytes(&mut byte);
+                *b = byte[0];
+            }
+        }
+        let em = [&[0x00, 0x02], &ps[..], &[0x00], msg].concat();
+
+        let m = os2ip(&em)?;
+        let c = self.rsaep(&m)?;
+        i2osp(&c, k)
+    }
+}
+
+impl PrivateKey {
+    // RSAES-PKCS1-V1_5-DECRYPT (RFC 8017 7.2.2) with implicit rejection
+    // (draft-irtf-cfrg-rsa-guidance): on a padding failure a pseudo-random message
+    // derived from the key and the ciphertext is returned instead of an error, so
+    // the result cannot be used as a Bleichenbacher padding oracle
+    pub fn decrypt_pkcs1v15(&self, ciphertext: &[u8]) -> Result<Vec<u8>> {
+        let mut rng = rand::thread_rng();
+        self.decrypt_pkcs1v15_with_rng(&mut rng, ciphertext)
+    }
+
+    // `rng` blinds RSADP
+    pub fn decrypt_pkcs1v15_with_rng<R: RngCore + CryptoRng>(
+        &self,
+        rng: &mut R,
+        ciphertext: &[u8],
+    ) -> Result<Vec<u8>> {
+        let k = self.size();
+        if k != ciphertext.len() || k < 11 {
+            return Err(Error::DecryptionError);
+        }
+
+        let c = os2ip(ciphertext)?;
+        let m = self
+            .rsadp_with_rng(rng, &c)
+            .map_err(|_| Error::DecryptionError)?;
+        let em = i2osp(&m, k)?;
+
+        // synthetic message, and its length chosen as the last of 128 candidates
+        // that fits, to avoid bias and a variable-time modular reduction
+        let kdk = implicit_rejection_kdk(self, ciphertext)?;
+        let synthetic = implicit_rejection_prf(&kdk, b"message", k)?;
+        let candidates = implicit_rejection_prf(&kdk, b"length", 256)?;
+
+        let max_sep_offset = (k - 10) as u16;
+        let mut len_mask = max_sep_offset;
+        len_mask |= len_mask >> 1;
+        len_mask |= len_mask >> 2;
+        len_mask |= len_mask >> 4;
+        len_mask |= len_mask >> 8;
+
+        let mut synthetic_len: u16 = 0;
+        for pair in candidates.chunks(2) {
+            let candidate = u16::from_be_bytes([pair[0], pair[1]]) & len_mask;
+            synthetic_len.conditional_assign(&candidate, candidate.ct_lt(&max_sep_offset));
+        }
+        let synthetic_index = k as u32 - synthetic_len as u32;
+
+        // EM = 0x00 || 0x02 || PS || 0x00 || M, with at least 8 octets of PS
+        let mut good = em[0].ct_eq(&0) & em[1].ct_eq(&2);
+        let mut found_zero = 0_u8.ct_eq(&1);
+        let mut zero_index: u32 = 0;
+        for (i, b) in em.iter().enumerate().skip(2) {
+            let is_zero = b.ct_eq(&0);
+            zero_index.conditional_assign(&(i as u32), !found_zero & is_zero);
+            found_zero |= is_zero;
+        }
+        good &= zero_index.ct_gt(&9);
+
+        let mut msg_index = zero_index + 1;
+        msg_index.conditional_assign(&synthetic_index, !good);
+
+        // both buffers are read so that the memory access pattern does not depend on `good`
+        Ok((msg_index as usize..k)
+            .map(|i| u8::conditional_select(&synthetic[i], &em[i], good))
+            .collect())
+    }
+}
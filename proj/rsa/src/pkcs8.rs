@@ -0,0 +1,304 @@
+// PKCS #8 PrivateKeyInfo (RFC 5208, RFC 5958) and X.509 SubjectPublicKeyInfo
+// (RFC 5280 4.1) containers around the PKCS #1 keys
+use crate::{
+    der::{
+        self, Reader, OID_MGF1, OID_PBES2, OID_RSASSA_PSS, OID_RSA_ENCRYPTION, OID_SHA1,
+        OID_SHA224, OID_SHA256, OID_SHA384, OID_SHA512,
+    },
+    errors::{Error, Result},
+    pbes2, pem,
+    plain_rsa::{PrivateKey, PublicKey},
+};
+use zeroize::Zeroizing;
+
+pub const PRIVATE_KEY_LABEL: &str = "PRIVATE KEY";
+pub const ENCRYPTED_PRIVATE_KEY_LABEL: &str = "ENCRYPTED PRIVATE KEY";
+pub const PUBLIC_KEY_LABEL: &str = "PUBLIC KEY";
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum HashAlgorithm {
+    Sha1,
+    Sha224,
+    Sha256,
+    Sha384,
+    Sha512,
+}
+
+impl HashAlgorithm {
+    fn oid(&self) -> &'static [u8] {
+        match self {
+            HashAlgorithm::Sha1 => OID_SHA1,
+            HashAlgorithm::Sha224 => OID_SHA224,
+            HashAlgorithm::Sha256 => OID_SHA256,
+            HashAlgorithm::Sha384 => OID_SHA384,
+            HashAlgorithm::Sha512 => OID_SHA512,
+        }
+    }
+
+    fn from_oid(oid: &[u8]) -> Result<Self> {
+        match oid {
+            OID_SHA1 => Ok(HashAlgorithm::Sha1),
+            OID_SHA224 => Ok(HashAlgorithm::Sha224),
+            OID_SHA256 => Ok(HashAlgorithm::Sha256),
+            OID_SHA384 => Ok(HashAlgorithm::Sha384),
+            OID_SHA512 => Ok(HashAlgorithm::Sha512),
+            _ => Err(Error::MalformedEncoding),
+        }
+    }
+
+    // AlgorithmIdentifier with NULL parameters, as written by OpenSSL
+    fn encode(&self) -> Vec<u8> {
+        der::sequence(&[der::object_identifier(self.oid()), der::null()])
+    }
+
+    // The parameters may be NULL or absent (RFC 4055 2.1)
+    fn decode(reader: &mut Reader) -> Result<Self> {
+        let mut seq = reader.read_sequence()?;
+        let hash = HashAlgorithm::from_oid(seq.read_object_identifier()?)?;
+        if !seq.is_empty() {
+            seq.read_null()?;
+        }
+        seq.finish()?;
+        Ok(hash)
+    }
+}
+
+// RSASSA-PSS-params (RFC 8017 A.2.3); the trailer field is always 0xbc
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct PssParams {
+    pub hash: HashAlgorithm,
+    pub mgf_hash: HashAlgorithm,
+    pub salt_len: usize,
+}
+
+impl Default for PssParams {
+    fn default() -> Self {
+        PssParams {
+            hash: HashAlgorithm::Sha1,
+            mgf_hash: HashAlgorithm::Sha1,
+            salt_len: 20,
+        }
+    }
+}
+
+impl PssParams {
+    // DER leaves out the fields that have their default value
+    fn encode(&self) -> Vec<u8> {
+        let default = PssParams::default();
+        let mut fields = vec![];
+        if self.hash != default.hash {
+            fields.push(der::encode(0xa0, &self.hash.encode()));
+        }
+        if self.mgf_hash != default.mgf_hash {
+            let mgf = der::sequence(&[der::object_identifier(OID_MGF1), self.mgf_hash.encode()]);
+            fields.push(der::encode(0xa1, &mgf));
+        }
+        if self.salt_len != default.salt_len {
+            let salt_len = der::integer(&(self.salt_len as u64).into());
+            fields.push(der::encode(0xa2, &salt_len));
+        }
+        der::sequence(&fields)
+    }
+
+    fn decode(reader: &mut Reader) -> Result<Self> {
+        let mut seq = reader.read_sequence()?;
+        let mut params = PssParams::default();
+
+        if seq.peek_tag() == Some(0xa0) {
+            params.hash = HashAlgorithm::decode(&mut Reader::new(seq.read(0xa0)?))?;
+        }
+        if seq.peek_tag() == Some(0xa1) {
+            let mut field = Reader::new(seq.read(0xa1)?);
+            let mut mgf = field.read_sequence()?;
+            if mgf.read_object_identifier()? != OID_MGF1 {
+                return Err(Error::MalformedEncoding);
+            }
+            params.mgf_hash = HashAlgorithm::decode(&mut mgf)?;
+            mgf.finish()?;
+            field.finish()?;
+        }
+        if seq.peek_tag() == Some(0xa2) {
+            let mut field = Reader::new(seq.read(0xa2)?);
+            params.salt_len = field.read_small_integer()? as usize;
+            field.finish()?;
+        }
+        if seq.peek_tag() == Some(0xa3) {
+            let mut field = Reader::new(seq.read(0xa3)?);
+            if field.read_small_integer()? != 1 {
+                return Err(Error::MalformedEncoding);
+            }
+            field.finish()?;
+        }
+        seq.finish()?;
+        Ok(params)
+    }
+}
+
+// Algorithm of an RSA key container; a PSS key may restrict its parameters
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum KeyAlgorithm {
+    RsaEncryption,
+    RsassaPss(Option<PssParams>),
+}
+
+impl KeyAlgorithm {
+    fn encode(&self) -> Vec<u8> {
+        match self {
+            KeyAlgorithm::RsaEncryption => {
+                der::sequence(&[der::object_identifier(OID_RSA_ENCRYPTION), der::null()])
+            }
+            KeyAlgorithm::RsassaPss(None) => {
+                der::sequence(&[der::object_identifier(OID_RSASSA_PSS)])
+            }
+            KeyAlgorithm::RsassaPss(Some(params)) => {
+                der::sequence(&[der::object_identifier(OID_RSASSA_PSS), params.encode()])
+            }
+        }
+    }
+
+    fn decode(reader: &mut Reader) -> Result<Self> {
+        let mut seq = reader.read_sequence()?;
+        let algorithm = match seq.read_object_identifier()? {
+            OID_RSA_ENCRYPTION => {
+                seq.read_null()?;
+                KeyAlgorithm::RsaEncryption
+            }
+            OID_RSASSA_PSS if seq.is_empty() => KeyAlgorithm::RsassaPss(None),
+            OID_RSASSA_PSS => KeyAlgorithm::RsassaPss(Some(PssParams::decode(&mut seq)?)),
+            _ => return Err(Error::WrongKeyType),
+        };
+        seq.finish()?;
+        Ok(algorithm)
+    }
+}
+
+impl PublicKey {
+    // SubjectPublicKeyInfo ::= SEQUENCE { algorithm, subjectPublicKey BIT STRING }
+    pub fn to_public_key_der(&self) -> Vec<u8> {
+        self.to_public_key_der_with_algorithm(&KeyAlgorithm::RsaEncryption)
+    }
+
+    pub fn to_public_key_der_with_algorithm(&self, algorithm: &KeyAlgorithm) -> Vec<u8> {
+        der::sequence(&[algorithm.encode(), der::bit_string(&self.to_pkcs1_der())])
+    }
+
+    pub fn from_public_key_der(bytes: &[u8]) -> Result<Self> {
+        Ok(PublicKey::from_public_key_der_with_algorithm(bytes)?.0)
+    }
+
+    pub fn from_public_key_der_with_algorithm(bytes: &[u8]) -> Result<(Self, KeyAlgorithm)> {
+        let mut outer = Reader::new(bytes);
+        let mut seq = outer.read_sequence()?;
+        let algorithm = KeyAlgorithm::decode(&mut seq)?;
+        let pk = PublicKey::from_pkcs1_der(seq.read_bit_string()?)?;
+        seq.finish()?;
+        outer.finish()?;
+        Ok((pk, algorithm))
+    }
+
+    pub fn to_public_key_pem(&self) -> String {
+        pem::encode(PUBLIC_KEY_LABEL, &self.to_public_key_der())
+    }
+
+    pub fn from_public_key_pem(text: &str) -> Result<Self> {
+        PublicKey::from_public_key_der(&pem::decode_label(text, PUBLIC_KEY_LABEL)?)
+    }
+}
+
+impl PrivateKey {
+    // PrivateKeyInfo ::= SEQUENCE { version, privateKeyAlgorithm, privateKey OCTET STRING }
+    pub fn to_pkcs8_der(&self) -> Vec<u8> {
+        self.to_pkcs8_der_with_algorithm(&KeyAlgorithm::RsaEncryption)
+    }
+
+    pub fn to_pkcs8_der_with_algorithm(&self, algorithm: &KeyAlgorithm) -> Vec<u8> {
+        der::sequence(&[
+            der::integer(&0_u32.into()),
+            algorithm.encode(),
+            der::octet_string(&Zeroizing::new(self.to_pkcs1_der())),
+        ])
+    }
+
+    pub fn from_pkcs8_der(bytes: &[u8]) -> Result<Self> {
+        Ok(PrivateKey::from_pkcs8_der_with_algorithm(bytes)?.0)
+    }
+
+    // Version 2 (OneAsymmetricKey) documents are accepted, their attributes and
+    // public key copy are skipped
+    pub fn from_pkcs8_der_with_algorithm(bytes: &[u8]) -> Result<(Self, KeyAlgorithm)> {
+        let mut outer = Reader::new(bytes);
+        let mut seq = outer.read_sequence()?;
+        let version = seq.read_small_integer()?;
+        if version > 1 {
+            return Err(Error::MalformedEncoding);
+        }
+        let algorithm = KeyAlgorithm::decode(&mut seq)?;
+        let sk = PrivateKey::from_pkcs1_der(seq.read_octet_string()?)?;
+        if seq.peek_tag() == Some(0xa0) {
+            seq.read(0xa0)?;
+        }
+        if version == 1 && seq.peek_tag() == Some(0x81) {
+            seq.read(0x81)?;
+        }
+        seq.finish()?;
+        outer.finish()?;
+        Ok((sk, algorithm))
+    }
+
+    pub fn to_pkcs8_pem(&self) -> String {
+        pem::encode(PRIVATE_KEY_LABEL, &Zeroizing::new(self.to_pkcs8_der()))
+    }
+
+    pub fn from_pkcs8_pem(text: &str) -> Result<Self> {
+        PrivateKey::from_pkcs8_der(&pem::decode_label(text, PRIVATE_KEY_LABEL)?)
+    }
+}
+
+impl PrivateKey {
+    // EncryptedPrivateKeyInfo ::= SEQUENCE { encryptionAlgorithm, encryptedData OCTET STRING }
+    // with PBES2 as the encryption algorithm
+    pub fn to_pkcs8_encrypted_der(
+        &self,
+        params: &pbes2::Parameters,
+        password: &[u8],
+    ) -> Result<Vec<u8>> {
+        let encrypted = params.encrypt(password, &Zeroizing::new(self.to_pkcs8_der()))?;
+        Ok(der::sequence(&[
+            der::sequence(&[der::object_identifier(OID_PBES2), params.encode()]),
+            der::octet_string(&encrypted),
+        ]))
+    }
+
+    // Anything that does not decrypt to a valid PrivateKeyInfo is reported as a
+    // wrong password, since CBC padding alone cannot tell
+    pub fn from_pkcs8_encrypted_der(bytes: &[u8], password: &[u8]) -> Result<Self> {
+        let mut outer = Reader::new(bytes);
+        let mut seq = outer.read_sequence()?;
+        let mut algorithm = seq.read_sequence()?;
+        if algorithm.read_object_identifier()? != OID_PBES2 {
+            return Err(Error::UnsupportedAlgorithm);
+        }
+        let params = pbes2::Parameters::decode(&mut algorithm)?;
+        algorithm.finish()?;
+        let encrypted = seq.read_octet_string()?;
+        seq.finish()?;
+        outer.finish()?;
+
+        let decrypted = Zeroizing::new(params.decrypt(password, encrypted)?);
+        PrivateKey::from_pkcs8_der(&decrypted).map_err(|_| Error::WrongPassword)
+    }
+
+    pub fn to_pkcs8_encrypted_pem(
+        &self,
+        params: &pbes2::Parameters,
+        password: &[u8],
+    ) -> Result<String> {
+        let der = self.to_pkcs8_encrypted_der(params, password)?;
+        Ok(pem::encode(ENCRYPTED_PRIVATE_KEY_LABEL, &der))
+    }
+
+    pub fn from_pkcs8_encrypted_pem(text: &str, password: &[u8]) -> Result<Self> {
+        let der = pem::decode_label(text, ENCRYPTED_PRIVATE_KEY_LABEL)?;
+        PrivateKey::from_pkcs8_encrypted_der(&der, password)
+    }
+}
@@ -1,26 +1,60 @@
 // Plain RSA
-use std::fs::File;
-use std::io::{BufRead, BufReader};
+use std::fs;
 use std::path::Path;
-use std::str;
 
 use base64::{engine::general_purpose, Engine};
-use num::Num;
-use num_bigint::BigUint;
+use num::{Num, One};
+use num_bigint::{BigUint, RandBigInt};
+use rand::{CryptoRng, RngCore};
 
+use crate::algorithms::{is_prime, mod_inverse, recover_primes};
+use crate::convert::{i2osp, os2ip};
 use crate::errors::{Error, Result};
 use crate::generator::Generator;
+use crate::secret::Secret;
+use crate::{pem, pkcs1, pkcs8, ssh};
 
-#[derive(Clone, PartialEq)]
+// Exponentiation with a secret exponent; the `ct` feature switches it to the
+// constant-time Montgomery backend
+#[cfg(feature = "ct")]
+fn secret_modpow(base: &BigUint, exp: &BigUint, modulus: &BigUint) -> BigUint {
+    crate::montgomery::ct_modpow(base, exp, modulus)
+}
+
+#[cfg(not(feature = "ct"))]
+fn secret_modpow(base: &BigUint, exp: &BigUint, modulus: &BigUint) -> BigUint {
+    base.modpow(exp, modulus)
+}
+
+#[derive(Clone, Debug, PartialEq)]
 pub struct PublicKey {
     pub n: BigUint,
     pub e: BigUint,
 }
 
-#[derive(Clone, PartialEq)]
+// The secret members are wiped on drop and left out of `Debug`
+#[derive(Clone, Debug, PartialEq)]
 pub struct PrivateKey {
     pub n: BigUint,
-    pub d: BigUint,
+    pub e: BigUint,
+    pub d: Secret,
+    // CRT representation (RFC 8017 3.2): p, q, d mod (p - 1), d mod (q - 1), q^-1 mod p
+    pub p: Secret,
+    pub q: Secret,
+    pub dp: Secret,
+    pub dq: Secret,
+    pub qinv: Secret,
+    // primes beyond p and q of a multi-prime key, empty for two primes
+    pub other_primes: Vec<OtherPrimeInfo>,
+}
+
+// Prime r_i of a multi-prime key with its CRT exponent d_i = d mod (r_i - 1)
+// and coefficient t_i = (r_1 * ... * r_(i-1))^-1 mod r_i (RFC 8017 3.2)
+#[derive(Clone, Debug, PartialEq)]
+pub struct OtherPrimeInfo {
+    pub prime: Secret,
+    pub exponent: Secret,
+    pub coefficient: Secret,
 }
 
 impl From<&Generator> for PublicKey {
@@ -32,37 +66,42 @@ impl From<&Generator> for PublicKey {
     }
 }
 
-impl From<&Path> for PublicKey {
-    fn from(path: &Path) -> Self {
-        let decode = |text: String| general_purpose::STANDARD.decode(text.clone()).unwrap();
-
-        let pk_path = path.to_str().unwrap();
-        match File::open(&pk_path) {
-            Ok(file) => {
-                let mut lines = vec![];
-                for line in BufReader::new(file).lines() {
-                    lines.push(line.expect("Fail in reading file"));
-                }
-
-                let n = BigUint::from_radix_be(
-                    str::from_utf8(&decode(lines.remove(1))).unwrap().as_bytes(),
-                    16u32,
-                )
-                .unwrap();
-                let e = BigUint::from_radix_be(
-                    str::from_utf8(&decode(lines.remove(1))).unwrap().as_bytes(),
-                    16u32,
-                )
-                .unwrap();
-
-                let pk = PublicKey::new(&n, &e).unwrap();
-                return pk;
-            }
-            Err(_) => panic!("Fail to load Public Key from path: {}", pk_path),
-        };
+impl TryFrom<&Path> for PublicKey {
+    type Error = Error;
+
+    fn try_from(path: &Path) -> Result<Self> {
+        PublicKey::load(path)
     }
 }
 
+fn read_key_file(path: &Path) -> Result<String> {
+    let bytes = fs::read(path)?;
+    String::from_utf8(bytes).map_err(|_| Error::MalformedEncoding)
+}
+
+// The format written by `Generator::print_to_file` before it switched to PKCS #1:
+// a banner, then one line per integer holding the base64 of its hex digits
+const LEGACY_PUBLIC_BANNER: &str = "---------- BEGIN RSA PUBLIC KEY ----------";
+const LEGACY_PRIVATE_BANNER: &str = "---------- BEGIN RSA PRIVATE KEY ----------";
+
+fn decode_legacy(text: &str, banner: &str) -> Result<(BigUint, BigUint)> {
+    let lines: Vec<&str> = text.lines().collect();
+    if lines.first() != Some(&banner) {
+        return Err(Error::MalformedEncoding);
+    }
+    if lines.len() < 3 {
+        return Err(Error::TruncatedFile);
+    }
+
+    let decode = |line: &str| {
+        let digits = general_purpose::STANDARD
+            .decode(line.trim())
+            .map_err(|_| Error::MalformedEncoding)?;
+        BigUint::from_radix_be(&digits, 16u32).ok_or(Error::MalformedEncoding)
+    };
+    Ok((decode(lines[1])?, decode(lines[2])?))
+}
+
 impl PublicKey {
     pub fn new(_n: &BigUint, _e: &BigUint) -> Result<Self> {
         Ok(PublicKey {
@@ -71,85 +110,319 @@ impl PublicKey {
         })
     }
 
-    pub fn encrypt_plain(&self, msg: &str) -> Result<BigUint> {
-        if !msg.is_ascii() {
-            Err(Error::MessageNotASCII)
-        } else {
-            let msg_code: BigUint = BigUint::from_bytes_be(msg.as_bytes());
-            Ok(msg_code.modpow(&self.e, &self.n))
+    // Reads a key file in the legacy format, for migration to PKCS #1
+    pub fn from_legacy(text: &str) -> Result<Self> {
+        let (n, e) = decode_legacy(text, LEGACY_PUBLIC_BANNER)?;
+        PublicKey::new(&n, &e)
+    }
+
+    // Reads a public key file written as PKCS #1 or SubjectPublicKeyInfo PEM, as
+    // an OpenSSH `ssh-rsa` line, or in the legacy format
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let text = read_key_file(path.as_ref())?;
+        if text.starts_with(LEGACY_PUBLIC_BANNER) {
+            return PublicKey::from_legacy(&text);
+        }
+        if text.starts_with(LEGACY_PRIVATE_BANNER) {
+            return Err(Error::WrongKeyType);
+        }
+        let start = text.trim_start();
+        if !start.is_empty() && !start.starts_with("-----") {
+            return PublicKey::from_openssh(&text);
+        }
+
+        let (label, der) = pem::decode(&text)?;
+        match label.as_str() {
+            pkcs1::PUBLIC_KEY_LABEL => PublicKey::from_pkcs1_der(&der),
+            pkcs8::PUBLIC_KEY_LABEL => PublicKey::from_public_key_der(&der),
+            _ => Err(Error::WrongKeyType),
         }
     }
 
-    pub fn encrypt(&self, msg: &str) -> Result<String> {
-        if !msg.is_ascii() {
-            Err(Error::MessageNotASCII)
-        } else {
-            let msg_code: BigUint = BigUint::from_bytes_be(msg.as_bytes());
-            Ok(format!(
-                "{}",
-                msg_code.modpow(&self.e, &self.n).to_str_radix(16u32)
-            ))
+    // Length of the modulus in octets, i.e. k in RFC 8017
+    pub fn size(&self) -> usize {
+        self.n.bits().div_ceil(8) as usize
+    }
+
+    // RSAEP (RFC 8017 5.1.1): c = m^e mod n, for 0 <= m < n
+    pub fn rsaep(&self, m: &BigUint) -> Result<BigUint> {
+        if m >= &self.n {
+            return Err(Error::RepresentativeOutOfRange);
         }
+        Ok(m.modpow(&self.e, &self.n))
     }
-}
 
-impl From<&Generator> for PrivateKey {
-    fn from(value: &Generator) -> Self {
-        PrivateKey {
-            n: value.n.clone(),
-            d: value.d.clone(),
+    // RSAVP1 (RFC 8017 5.2.2) is the same exponentiation as RSAEP
+    pub fn rsavp1(&self, s: &BigUint) -> Result<BigUint> {
+        self.rsaep(s)
+    }
+
+    // Raw RSA over octet strings: I2OSP(RSAEP(OS2IP(msg)), k)
+    pub fn encrypt_bytes(&self, msg: &[u8]) -> Result<Vec<u8>> {
+        let k = self.size();
+        if msg.len() > k {
+            return Err(Error::MessageTooLong);
         }
+
+        let m = os2ip(msg)?;
+        let c = self.rsaep(&m)?;
+        i2osp(&c, k)
+    }
+
+    pub fn encrypt_plain(&self, msg: &str) -> Result<BigUint> {
+        let m = os2ip(msg.as_bytes())?;
+        self.rsaep(&m)
+    }
+
+    pub fn encrypt(&self, msg: &str) -> Result<String> {
+        let ciphertext = self.encrypt_bytes(msg.as_bytes())?;
+        Ok(os2ip(&ciphertext)?.to_str_radix(16u32))
     }
 }
-impl From<&Path> for PrivateKey {
-    fn from(path: &Path) -> Self {
-        let decode = |text: String| general_purpose::STANDARD.decode(text.clone()).unwrap();
-
-        let sk_path = path.to_str().unwrap();
-        match File::open(&sk_path) {
-            Ok(file) => {
-                let mut lines = vec![];
-                for line in BufReader::new(file).lines() {
-                    lines.push(line.expect("Fail in reading file"));
-                }
-
-                let n = BigUint::from_radix_be(
-                    str::from_utf8(&decode(lines.remove(1))).unwrap().as_bytes(),
-                    16u32,
-                )
-                .unwrap();
-                let e = BigUint::from_radix_be(
-                    str::from_utf8(&decode(lines.remove(1))).unwrap().as_bytes(),
-                    16u32,
-                )
-                .unwrap();
-
-                let sk = PrivateKey::new(&n, &e).unwrap();
-                return sk;
-            }
-            Err(_) => panic!("Fail to load Public Key from path: {}", sk_path),
-        };
+
+impl TryFrom<&Generator> for PrivateKey {
+    type Error = Error;
+
+    fn try_from(value: &Generator) -> Result<Self> {
+        PrivateKey::from_primes(&value.n, &value.e, &value.d, &value.primes())
+    }
+}
+
+impl TryFrom<&Path> for PrivateKey {
+    type Error = Error;
+
+    fn try_from(path: &Path) -> Result<Self> {
+        PrivateKey::load(path)
     }
 }
 
 impl PrivateKey {
-    pub fn new(_n: &BigUint, _d: &BigUint) -> Result<Self> {
+    // The prime factors are recovered from (n, e, d): a composite factor of a
+    // multi-prime n is split again, as e * d - 1 is a multiple of its lambda too
+    pub fn new(_n: &BigUint, _e: &BigUint, _d: &BigUint) -> Result<Self> {
+        let mut factors = vec![Secret::new(_n.clone())];
+        let mut primes = vec![];
+        while let Some(m) = factors.pop() {
+            if is_prime(&m) {
+                primes.push(m);
+                continue;
+            }
+            let (a, b) = recover_primes(&m, _e, _d)
+                .ok_or(Error::InvalidKeyPair("n cannot be factored with e and d"))?;
+            factors.push(Secret::new(a));
+            factors.push(Secret::new(b));
+        }
+        primes.sort_by(|a, b| b.cmp(a));
+        let primes: Vec<&BigUint> = primes.iter().map(|r| &**r).collect();
+        PrivateKey::from_primes(_n, _e, _d, &primes)
+    }
+
+    pub fn from_components(
+        _n: &BigUint,
+        _e: &BigUint,
+        _d: &BigUint,
+        _p: &BigUint,
+        _q: &BigUint,
+    ) -> Result<Self> {
+        PrivateKey::from_primes(_n, _e, _d, &[_p, _q])
+    }
+
+    // Multi-prime key from all the prime factors of n, p and q first
+    pub fn from_primes(
+        _n: &BigUint,
+        _e: &BigUint,
+        _d: &BigUint,
+        _primes: &[&BigUint],
+    ) -> Result<Self> {
+        let one = BigUint::one();
+        let product: BigUint = _primes.iter().copied().product();
+        if _primes.len() < 2 || _primes.iter().any(|&r| r <= &one) || &product != _n {
+            return Err(Error::InvalidKeyPair(if _primes.len() == 2 {
+                "p * q is not n"
+            } else {
+                "the product of the primes is not n"
+            }));
+        }
+        let (p, q) = (_primes[0], _primes[1]);
+        let qinv = mod_inverse(q, p).ok_or(Error::InvalidKeyPair("q is not invertible mod p"))?;
+
+        let mut other_primes = vec![];
+        let mut r_product = p * q;
+        for &r in &_primes[2..] {
+            let coefficient = mod_inverse(&r_product, r)
+                .ok_or(Error::InvalidKeyPair("the primes are not coprime"))?;
+            other_primes.push(OtherPrimeInfo {
+                prime: Secret::new(r.clone()),
+                exponent: Secret::new(_d % (r - &one)),
+                coefficient: Secret::new(coefficient),
+            });
+            r_product *= r;
+        }
+
         Ok(PrivateKey {
             n: _n.to_owned(),
-            d: _d.to_owned(),
+            e: _e.to_owned(),
+            d: Secret::new(_d.to_owned()),
+            p: Secret::new(p.clone()),
+            q: Secret::new(q.clone()),
+            dp: Secret::new(_d % (p - &one)),
+            dq: Secret::new(_d % (q - &one)),
+            qinv: Secret::new(qinv),
+            other_primes,
         })
     }
 
+    // p, q, then the other primes
+    pub fn primes(&self) -> impl Iterator<Item = &BigUint> {
+        [&*self.p, &*self.q]
+            .into_iter()
+            .chain(self.other_primes.iter().map(|info| &*info.prime))
+    }
+
+    // Reads a key file in the legacy format, for migration to PKCS #1; the format
+    // drops e, but Generator only ever wrote keys with e = 65537
+    pub fn from_legacy(text: &str) -> Result<Self> {
+        let (n, d) = decode_legacy(text, LEGACY_PRIVATE_BANNER)?;
+        PrivateKey::new(&n, &BigUint::from(65537_u32), &d)
+    }
+
+    // Reads an unencrypted private key file written as PKCS #1, PKCS #8 or
+    // OpenSSH PEM, or in the legacy format
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        PrivateKey::load_file(path.as_ref(), None)
+    }
+
+    // Same as `load`, and also reads encrypted PKCS #8 and OpenSSH key files
+    pub fn load_with_password<P: AsRef<Path>>(path: P, password: &[u8]) -> Result<Self> {
+        PrivateKey::load_file(path.as_ref(), Some(password))
+    }
+
+    fn load_file(path: &Path, password: Option<&[u8]>) -> Result<Self> {
+        let text = read_key_file(path)?;
+        if text.starts_with(LEGACY_PRIVATE_BANNER) {
+            return PrivateKey::from_legacy(&text);
+        }
+        if text.starts_with(LEGACY_PUBLIC_BANNER) {
+            return Err(Error::WrongKeyType);
+        }
+
+        let (label, der) = pem::decode(&text)?;
+        match label.as_str() {
+            pkcs1::PRIVATE_KEY_LABEL => PrivateKey::from_pkcs1_der(&der),
+            pkcs8::PRIVATE_KEY_LABEL => PrivateKey::from_pkcs8_der(&der),
+            ssh::PRIVATE_KEY_LABEL => PrivateKey::from_openssh_private(&text, password),
+            pkcs8::ENCRYPTED_PRIVATE_KEY_LABEL => {
+                let password = password.ok_or(Error::PasswordRequired)?;
+                PrivateKey::from_pkcs8_encrypted_der(&der, password)
+            }
+            _ => Err(Error::WrongKeyType),
+        }
+    }
+
+    // Length of the modulus in octets, i.e. k in RFC 8017
+    pub fn size(&self) -> usize {
+        self.n.bits().div_ceil(8) as usize
+    }
+
+    // RSADP (RFC 8017 5.1.2): m = c^d mod n, for 0 <= c < n
+    // Blinded with a thread-local RNG, see `rsadp_with_rng`
+    pub fn rsadp(&self, c: &BigUint) -> Result<BigUint> {
+        let mut rng = rand::thread_rng();
+        self.rsadp_with_rng(&mut rng, c)
+    }
+
+    // The exponentiation runs on c * r^e for a fresh random r and the result is
+    // multiplied by r^-1, so its timing is unrelated to the attacker-chosen c
+    pub fn rsadp_with_rng<R: RngCore + CryptoRng>(
+        &self,
+        rng: &mut R,
+        c: &BigUint,
+    ) -> Result<BigUint> {
+        if c >= &self.n {
+            return Err(Error::RepresentativeOutOfRange);
+        }
+
+        let (r, r_inv) = loop {
+            let r = rng.gen_biguint_below(&self.n);
+            if let Some(r_inv) = mod_inverse(&r, &self.n) {
+                break (r, r_inv);
+            }
+        };
+        let blinded = (c * r.modpow(&self.e, &self.n)) % &self.n;
+        let m = self.crt_exp(&blinded)?;
+        Ok((m * r_inv) % &self.n)
+    }
+
+    // RSASP1 (RFC 8017 5.2.1) is the same exponentiation as RSADP
+    pub fn rsasp1(&self, m: &BigUint) -> Result<BigUint> {
+        self.rsadp(m)
+    }
+
+    pub fn rsasp1_with_rng<R: RngCore + CryptoRng>(
+        &self,
+        rng: &mut R,
+        m: &BigUint,
+    ) -> Result<BigUint> {
+        self.rsadp_with_rng(rng, m)
+    }
+
+    // c^d mod n with Garner's CRT recombination, then checked by re-encrypting so
+    // that a faulty half-exponentiation cannot leak a factor of n (Bellcore attack)
+    // The half-exponentiations and the CRT coefficients are wiped on drop; the
+    // unnamed temporaries of the BigUint arithmetic are not
+    fn crt_exp(&self, c: &BigUint) -> Result<BigUint> {
+        let m1 = Secret::new(secret_modpow(c, &self.dp, &self.p));
+        let m2 = Secret::new(secret_modpow(c, &self.dq, &self.q));
+        // h = qInv * (m1 - m2) mod p
+        let (p, q) = (&*self.p, &*self.q);
+        let h = Secret::new((&*self.qinv * (&*m1 + p - &*m2 % p)) % p);
+        let mut m = &*m2 + &*h * q;
+
+        // each further prime lifts m from mod r_1 * ... * r_(i-1) to mod r_i too
+        // (RFC 8017 5.1.2 step 2.b)
+        let mut r_product = p * q;
+        for info in &self.other_primes {
+            let r = &*info.prime;
+            let m_i = Secret::new(secret_modpow(c, &info.exponent, r));
+            let h = Secret::new((&*info.coefficient * (&*m_i + r - &m % r)) % r);
+            m += &r_product * &*h;
+            r_product *= r;
+        }
+
+        if &m.modpow(&self.e, &self.n) != c {
+            return Err(Error::FaultDetected);
+        }
+        Ok(m)
+    }
+
+    // Raw RSA over octet strings: I2OSP(RSADP(OS2IP(ciphertext)), k)
+    pub fn decrypt_bytes(&self, ciphertext: &[u8]) -> Result<Vec<u8>> {
+        let k = self.size();
+        if ciphertext.len() > k {
+            return Err(Error::MessageTooLong);
+        }
+
+        let c = os2ip(ciphertext)?;
+        let m = self.rsadp(&c)?;
+        i2osp(&m, k)
+    }
+
     pub fn decrypt_plain(&self, ciphertext: &str) -> Result<BigUint> {
-        let c: BigUint = BigUint::from_str_radix(ciphertext, 16u32).unwrap();
-        let decrypt_plain = c.modpow(&self.d, &self.n);
-        Ok(decrypt_plain)
+        let c: BigUint =
+            BigUint::from_str_radix(ciphertext, 16u32).map_err(|_| Error::DecryptionError)?;
+        self.rsadp(&c)
     }
 
     pub fn decrypt(&self, ciphertext: &str) -> Result<String> {
-        let c: BigUint = BigUint::from_str_radix(ciphertext, 16u32).unwrap();
-        let decrypt_as_bytes: Vec<u8> = c.modpow(&self.d, &self.n).to_bytes_be();
-        let res_decrypt: &str = std::str::from_utf8(&decrypt_as_bytes).unwrap();
-        Ok(format!("{}", res_decrypt))
+        let c: BigUint =
+            BigUint::from_str_radix(ciphertext, 16u32).map_err(|_| Error::DecryptionError)?;
+        let plaintext = self.decrypt_bytes(&c.to_bytes_be())?;
+
+        // textbook RSA cannot tell leading zero octets apart from the k-octet padding
+        let start = plaintext
+            .iter()
+            .position(|&b| b != 0)
+            .unwrap_or(plaintext.len());
+        String::from_utf8(plaintext[start..].to_vec()).map_err(|_| Error::MessageNotUTF8)
     }
 }
@@ -0,0 +1,671 @@
+// Generation of the RSA primes p and q after FIPS 186-5 Appendix A.1: random
+// probable primes (A.1.3), and provable primes from a seed with the Shawe-Taylor
+// construction (A.1.2, FIPS 186-4 C.6 and C.10)
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::OnceLock;
+use std::thread;
+
+use num::{Integer, One, ToPrimitive, Zero};
+use num_bigint::{BigUint, RandBigInt};
+use rand::{CryptoRng, Rng, RngCore};
+use sha2::{Digest, Sha256};
+
+use crate::algorithms::{is_prime, miller_rabin_test};
+
+// Minimum rounds of Miller-Rabin for p and q (FIPS 186-5 Table B.1); shorter
+// moduli are outside of FIPS 186-5 and get 8 rounds
+pub fn miller_rabin_rounds(nlen: u64) -> u64 {
+    if nlen >= 3072 {
+        4
+    } else if nlen >= 2048 {
+        5
+    } else {
+        8
+    }
+}
+
+// floor(sqrt(2) * 2^(bits - 1)), the smallest value allowed for p and q
+pub fn lower_bound(bits: u64) -> BigUint {
+    (BigUint::one() << (2 * bits - 1)).sqrt()
+}
+
+// A.1.3 steps 4 and 5: a `bits`-bit probable prime p >= sqrt(2) * 2^(bits - 1)
+// with gcd(p - 1, e) = 1, at distance more than 2^(bits - 100) from `other` if
+// given. None after 5 * bits candidates, upon which the caller starts over.
+pub fn probable_prime<R: CryptoRng + RngCore>(
+    rng: &mut R,
+    bits: u64,
+    e: &BigUint,
+    rounds: u64,
+    other: Option<&BigUint>,
+) -> Option<BigUint> {
+    probable_prime_until(rng, bits, e, rounds, other, &AtomicBool::new(false))
+}
+
+// `probable_prime`, giving up with None as soon as `stop` is set
+fn probable_prime_until<R: CryptoRng + RngCore>(
+    rng: &mut R,
+    bits: u64,
+    e: &BigUint,
+    rounds: u64,
+    other: Option<&BigUint>,
+    stop: &AtomicBool,
+) -> Option<BigUint> {
+    let one = BigUint::one();
+    let bound = lower_bound(bits);
+    let min_distance = &one << bits.saturating_sub(100);
+
+    for _ in 0..5 * bits {
+        if stop.load(Ordering::Relaxed) {
+            return None;
+        }
+        let mut p = rng.gen_biguint(bits);
+        p.set_bit(0, true);
+        if p < bound {
+            continue;
+        }
+        if let Some(other) = other {
+            let distance = if &p > other { &p - other } else { other - &p };
+            if distance <= min_distance {
+                continue;
+            }
+        }
+        if (&p - &one).gcd(e).is_one() && miller_rabin_test(rng, &p, rounds) {
+            return Some(p);
+        }
+    }
+    None
+}
+
+// Odd primes below SIEVE_LIMIT, whose multiples the sieve skips
+const SIEVE_LIMIT: usize = 1 << 14;
+
+fn small_primes() -> &'static [u32] {
+    static PRIMES: OnceLock<Vec<u32>> = OnceLock::new();
+    PRIMES.get_or_init(|| {
+        let mut composite = vec![false; SIEVE_LIMIT];
+        let mut primes = vec![];
+        for i in (3..SIEVE_LIMIT).step_by(2) {
+            if !composite[i] {
+                primes.push(i as u32);
+                for j in (i * i..SIEVE_LIMIT).step_by(2 * i) {
+                    composite[j] = true;
+                }
+            }
+        }
+        primes
+    })
+}
+
+// How the builder looks for p and q
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum PrimeSearch {
+    // a fresh random candidate each time, as FIPS 186-5 A.1.3 requires
+    #[default]
+    Fips,
+    // `sieve_prime`, much faster but not FIPS compliant
+    Sieve,
+}
+
+// Same contract as `probable_prime`, by incremental search: from a random odd
+// start, step by 2 and keep the residues modulo the small primes up to date, so
+// that Miller-Rabin only runs on candidates without a small factor. None when
+// the search runs past 2^bits.
+pub fn sieve_prime<R: CryptoRng + RngCore>(
+    rng: &mut R,
+    bits: u64,
+    e: &BigUint,
+    rounds: u64,
+    other: Option<&BigUint>,
+) -> Option<BigUint> {
+    sieve_prime_until(rng, bits, e, rounds, other, &AtomicBool::new(false))
+}
+
+// `sieve_prime`, giving up with None as soon as `stop` is set
+fn sieve_prime_until<R: CryptoRng + RngCore>(
+    rng: &mut R,
+    bits: u64,
+    e: &BigUint,
+    rounds: u64,
+    other: Option<&BigUint>,
+    stop: &AtomicBool,
+) -> Option<BigUint> {
+    // the candidates have to exceed the sieving primes
+    if bits < 16 {
+        return probable_prime_until(rng, bits, e, rounds, other, stop);
+    }
+    let one = BigUint::one();
+    let top = &one << bits;
+    let min_distance = &one << bits.saturating_sub(100);
+    let table = small_primes();
+
+    let mut start = rng.gen_biguint_range(&lower_bound(bits), &top);
+    start.set_bit(0, true);
+    let room = (&top - &start).to_u64().unwrap_or(u64::MAX);
+    let mut residues: Vec<u32> = table
+        .iter()
+        .map(|&p| (&start % p).to_u32().unwrap())
+        .collect();
+
+    let mut delta: u64 = 0;
+    while delta < room {
+        if residues.iter().all(|&r| r != 0) {
+            if stop.load(Ordering::Relaxed) {
+                return None;
+            }
+            let p = &start + delta;
+            let far = match other {
+                Some(other) => (if &p > other { &p - other } else { other - &p }) > min_distance,
+                None => true,
+            };
+            if far && (&p - &one).gcd(e).is_one() && miller_rabin_test(rng, &p, rounds) {
+                return Some(p);
+            }
+        }
+        for (r, &p) in residues.iter_mut().zip(table) {
+            *r += 2;
+            if *r >= p {
+                *r -= p;
+            }
+        }
+        delta += 2;
+    }
+    None
+}
+
+// Same contract as `search`, on `threads` workers that each draw from their own
+// thread-local RNG: the first prime found is returned, and the other workers
+// stop at their next candidate
+pub fn parallel_prime(
+    search: PrimeSearch,
+    threads: usize,
+    bits: u64,
+    e: &BigUint,
+    rounds: u64,
+    other: Option<&BigUint>,
+) -> BigUint {
+    let stop = AtomicBool::new(false);
+    thread::scope(|scope| {
+        let workers: Vec<_> = (0..threads.max(1))
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut rng = rand::thread_rng();
+                    while !stop.load(Ordering::Relaxed) {
+                        let found = match search {
+                            PrimeSearch::Fips => {
+                                probable_prime_until(&mut rng, bits, e, rounds, other, &stop)
+                            }
+                            PrimeSearch::Sieve => {
+                                sieve_prime_until(&mut rng, bits, e, rounds, other, &stop)
+                            }
+                        };
+                        if found.is_some() {
+                            stop.store(true, Ordering::Relaxed);
+                            return found;
+                        }
+                    }
+                    None
+                })
+            })
+            .collect();
+        // only a worker that found a prime sets `stop`
+        workers
+            .into_iter()
+            .filter_map(|worker| worker.join().unwrap())
+            .next()
+            .unwrap()
+    })
+}
+
+const OUTLEN: u64 = 256;
+
+// Seeds are integers of a fixed octet length that get incremented; they are
+// hashed as big-endian strings of that length
+struct Seed {
+    value: BigUint,
+    len: usize,
+}
+
+impl Seed {
+    fn hash(&self, offset: u64) -> BigUint {
+        let value = (&self.value + offset) % (BigUint::one() << (8 * self.len));
+        let bytes = value.to_bytes_be();
+        let mut input = vec![0_u8; self.len - bytes.len().min(self.len)];
+        input.extend_from_slice(&bytes);
+        BigUint::from_bytes_be(&Sha256::digest(&input))
+    }
+
+    // sum of Hash(seed + i) * 2^(i * outlen) for i = 0 to iterations, then
+    // seed = seed + iterations + 1
+    fn expand(&mut self, iterations: u64) -> BigUint {
+        let mut x = BigUint::zero();
+        for i in 0..=iterations {
+            x += self.hash(i) << (i * OUTLEN);
+        }
+        self.value += iterations + 1;
+        x
+    }
+}
+
+// Trial division, enough below 2^33
+fn is_small_prime(c: u64) -> bool {
+    if c < 2 {
+        return false;
+    }
+    if c.is_even() {
+        return c == 2;
+    }
+    let mut i = 3;
+    while i * i <= c {
+        if c.is_multiple_of(i) {
+            return false;
+        }
+        i += 2;
+    }
+    true
+}
+
+// ST_Random_Prime (FIPS 186-4 C.6): a `length`-bit prime, proven by its
+// construction from a smaller proven prime c0 with Pocklington's criterion
+fn shawe_taylor_random_prime(length: u64, seed: &mut Seed) -> Option<BigUint> {
+    if length < 2 {
+        return None;
+    }
+    let one = BigUint::one();
+    let top = &one << (length - 1);
+
+    if length < 33 {
+        for _ in 0..=4 * length {
+            let c = seed.hash(0) ^ seed.hash(1);
+            seed.value += 2_u32;
+            let c = (&top + (c % &top)) | &one;
+            let small = u64::try_from(&c).ok()?;
+            if is_small_prime(small) {
+                return Some(c);
+            }
+        }
+        return None;
+    }
+
+    let c0 = shawe_taylor_random_prime(length.div_ceil(2) + 1, seed)?;
+    let iterations = length.div_ceil(OUTLEN) - 1;
+    let x = &top + (seed.expand(iterations) % &top);
+    let two_c0 = &c0 << 1_u32;
+    let mut t = x.div_ceil(&two_c0);
+
+    for _ in 0..4 * length {
+        if &t * &two_c0 + &one > &top << 1 {
+            t = top.div_ceil(&two_c0);
+        }
+        let c = &t * &two_c0 + &one;
+        let a = BigUint::from(2_u32) + seed.expand(iterations) % (&c - 3_u32);
+        let z = a.modpow(&(&t << 1), &c);
+        if (&z - &one).gcd(&c).is_one() && z.modpow(&c0, &c).is_one() {
+            return Some(c);
+        }
+        t += 1_u32;
+    }
+    None
+}
+
+// Provable_Prime_Construction (FIPS 186-4 C.10) with N1 = N2 = 1: an `l`-bit
+// prime p >= sqrt(2) * 2^(l - 1) with gcd(p - 1, e) = 1
+fn provable_prime(l: u64, e: &BigUint, seed: &mut Seed) -> Option<BigUint> {
+    let one = BigUint::one();
+    let p0 = shawe_taylor_random_prime(l.div_ceil(2) + 1, seed)?;
+    let iterations = l.div_ceil(OUTLEN) - 1;
+
+    let bound = lower_bound(l);
+    let top = &one << l;
+    let x = &bound + seed.expand(iterations) % (&top - &bound);
+    let two_p0 = &p0 << 1_u32;
+    // y = 1 as p1 = p2 = 1, so p = 2 (t - 1) p0 + 1
+    let mut t = (x + &two_p0).div_ceil(&two_p0);
+
+    for _ in 0..5 * l {
+        if (&t - &one) * &two_p0 + &one > top {
+            t = (&two_p0 + &bound).div_ceil(&two_p0);
+        }
+        let p = (&t - &one) * &two_p0 + &one;
+        if (&p - &one).gcd(e).is_one() {
+            let a = BigUint::from(2_u32) + seed.expand(iterations) % (&p - 3_u32);
+            let z = a.modpow(&((&t - &one) << 1), &p);
+            if (&z - &one).gcd(&p).is_one() && z.modpow(&p0, &p).is_one() {
+                return Some(p);
+            }
+        }
+        t += 1_u32;
+    }
+    None
+}
+
+// FIPS 186-4 B.3.2.2: p and q of `bits` bits each, derived from `seed` alone, so
+// that anyone holding the seed can check how they were generated
+pub fn provable_primes(bits: u64, e: &BigUint, seed: &[u8]) -> Option<(BigUint, BigUint)> {
+    let mut seed = Seed {
+        value: BigUint::from_bytes_be(seed),
+        len: seed.len(),
+    };
+    let p = provable_prime(bits, e, &mut seed)?;
+    let min_distance = BigUint::one() << bits.saturating_sub(100);
+    loop {
+        let q = provable_prime(bits, e, &mut seed)?;
+        let distance = if p > q { &p - &q } else { &q - &p };
+        if distance > min_distance {
+            return Some((p, q));
+        }
+    }
+}
+
+// Primes with structure, for other uses than RSA keys
+
+fn has_small_factor(n: &BigUint) -> bool {
+    small_primes()
+        .iter()
+        .any(|&p| (n % p).is_zero() && *n != BigUint::from(p))
+}
+
+// A random prime in [low, high): the search walks up from a random point and
+// wraps around, so None means that the range holds no prime
+pub fn random_prime_in_range<R: CryptoRng + RngCore>(
+    rng: &mut R,
+    low: &BigUint,
+    high: &BigUint,
+) -> Option<BigUint> {
+    if low >= high {
+        return None;
+    }
+    let start = rng.gen_biguint_range(low, high);
+    let mut n = start.clone();
+    loop {
+        if is_prime(&n) {
+            return Some(n);
+        }
+        n += 1_u32;
+        if &n == high {
+            n = low.clone();
+        }
+        if n == start {
+            return None;
+        }
+    }
+}
+
+// A `bits`-bit safe prime p = 2q + 1 with q prime, as used for Diffie-Hellman
+// groups. The sieve skips q when q or 2q + 1 has a small factor.
+pub fn safe_prime<R: CryptoRng + RngCore>(rng: &mut R, bits: u64) -> Option<BigUint> {
+    let one = BigUint::one();
+    if bits < 3 {
+        return None;
+    }
+    // q = (p - 1) / 2 has bits - 1 bits
+    let low = &one << (bits - 2);
+    let high = &one << (bits - 1);
+    // too short to sieve: pick one of all the safe primes of that length
+    if bits < 18 {
+        let mut all = vec![];
+        let mut q = low;
+        while q < high {
+            let p = (&q << 1_u32) + 1_u32;
+            if is_prime(&q) && is_prime(&p) {
+                all.push(p);
+            }
+            q += 1_u32;
+        }
+        let i = rng.gen_range(0..all.len());
+        return Some(all.swap_remove(i));
+    }
+
+    let table = small_primes();
+    loop {
+        let mut q = rng.gen_biguint_range(&low, &high);
+        q.set_bit(0, true);
+        let room = (&high - &q).to_u64().unwrap_or(u64::MAX);
+        let mut residues: Vec<u32> = table.iter().map(|&p| (&q % p).to_u32().unwrap()).collect();
+
+        let mut delta: u64 = 0;
+        while delta < room {
+            // p = 2q + 1 is a multiple of f when q = (f - 1) / 2 mod f
+            if residues
+                .iter()
+                .zip(table)
+                .all(|(&r, &f)| r != 0 && r != (f - 1) / 2)
+            {
+                let q = &q + delta;
+                let p = (&q << 1_u32) + 1_u32;
+                if is_prime(&q) && is_prime(&p) {
+                    return Some(p);
+                }
+            }
+            for (r, &f) in residues.iter_mut().zip(table) {
+                *r += 2;
+                if *r >= f {
+                    *r -= f;
+                }
+            }
+            delta += 2;
+        }
+    }
+}
+
+// Gordon's strong prime: p - 1 has the large prime factor r, p + 1 the large
+// prime factor s, and r - 1 the large prime factor t
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct StrongPrime {
+    pub p: BigUint,
+    pub r: BigUint,
+    pub s: BigUint,
+    pub t: BigUint,
+}
+
+// Shortest strong prime: below it, r and s leave too little room to place p
+pub const MIN_STRONG_PRIME_BITS: u64 = 64;
+
+// A `bits`-bit strong prime, None only below MIN_STRONG_PRIME_BITS. p is searched
+// from a random point among the p0 + 2jrs of that length, wrapping around, and
+// new s and t are drawn when none of them is prime.
+pub fn strong_prime<R: CryptoRng + RngCore>(rng: &mut R, bits: u64) -> Option<StrongPrime> {
+    if bits < MIN_STRONG_PRIME_BITS {
+        return None;
+    }
+    let one = BigUint::one();
+    let two = BigUint::from(2_u32);
+    // s and t leave bits / 16 bits of room to adjust the length of p
+    let aux_bits = bits / 2 - bits / 16;
+    let aux_prime = |rng: &mut R| loop {
+        if let Some(x) = sieve_prime(rng, aux_bits, &one, 8, None) {
+            return x;
+        }
+    };
+    let low = &one << (bits - 1);
+    let high = &one << bits;
+    loop {
+        let s = aux_prime(rng);
+        let t = aux_prime(rng);
+
+        // r = 2it + 1, the first prime of that form
+        let mut r = (&t << 1_u32) + 1_u32;
+        while !is_prime(&r) {
+            r += &t << 1_u32;
+        }
+
+        // p0 = 1 mod r and p0 = -1 mod s, then p = p0 + 2jrs
+        let p0 = ((s.modpow(&(&r - &two), &r) * &s) << 1_u32) - &one;
+        let step = (&r * &s) << 1_u32;
+        let first = if p0 < low {
+            &p0 + (&low - &p0).div_ceil(&step) * &step
+        } else {
+            p0
+        };
+        if first >= high {
+            continue;
+        }
+        let count = (&high - &first - 1_u32) / &step + 1_u32;
+        let start = rng.gen_biguint_below(&count);
+        let mut j = start.clone();
+        loop {
+            let p = &first + &j * &step;
+            if is_prime(&p) {
+                return Some(StrongPrime { p, r, s, t });
+            }
+            j += 1_u32;
+            if j == count {
+                j.set_zero();
+            }
+            if j == start {
+                break;
+            }
+        }
+    }
+}
+
+// Primality certificate after Pocklington: n - 1 = F * R where F is made of the
+// proven prime factors f (to their full power in n - 1), F^2 > n, and for the
+// witness a, a^(n - 1) = 1 and gcd(a^((n - 1) / f) - 1, n) = 1 mod n for each f.
+// With n - 1 fully factored, this is a Pratt certificate.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Certificate {
+    // below 2^33, checked by trial division
+    Small(u64),
+    Pocklington {
+        n: BigUint,
+        witness: BigUint,
+        factors: Vec<Certificate>,
+    },
+}
+
+impl Certificate {
+    pub fn prime(&self) -> BigUint {
+        match self {
+            Certificate::Small(n) => BigUint::from(*n),
+            Certificate::Pocklington { n, .. } => n.clone(),
+        }
+    }
+
+    // Checks the whole chain, without any probabilistic test
+    pub fn verify(&self) -> bool {
+        let (n, witness, factors) = match self {
+            Certificate::Small(n) => return *n < 1 << 33 && is_small_prime(*n),
+            Certificate::Pocklington {
+                n,
+                witness,
+                factors,
+            } => (n, witness, factors),
+        };
+        let one = BigUint::one();
+        if n < &BigUint::from(3_u32) || witness <= &one || witness >= n {
+            return false;
+        }
+        let n_minus_one = n - &one;
+
+        let mut f_part = one.clone();
+        let mut rest = n_minus_one.clone();
+        for factor in factors {
+            let f = factor.prime();
+            if !factor.verify() || !(&rest % &f).is_zero() {
+                return false;
+            }
+            while (&rest % &f).is_zero() {
+                rest /= &f;
+                f_part *= &f;
+            }
+            let z = witness.modpow(&(&n_minus_one / &f), n);
+            if z.is_zero() || !(z - &one).gcd(n).is_one() {
+                return false;
+            }
+        }
+        &f_part * &f_part > *n && witness.modpow(&n_minus_one, n).is_one()
+    }
+}
+
+// Pocklington check of n = 2 * k * f + 1 with the proven prime f > sqrt(n)
+fn pocklington<R: CryptoRng + RngCore>(
+    rng: &mut R,
+    n: &BigUint,
+    f: Certificate,
+) -> Option<Certificate> {
+    let one = BigUint::one();
+    let witness = rng.gen_biguint_range(&BigUint::from(2_u32), &(n - &one));
+    let certificate = Certificate::Pocklington {
+        n: n.clone(),
+        witness,
+        factors: vec![f],
+    };
+    certificate.verify().then_some(certificate)
+}
+
+// A `bits`-bit prime with its certificate, built as n = 2tc + 1 on a proven prime
+// c of about half the length (Maurer's method)
+pub fn certified_prime<R: CryptoRng + RngCore>(rng: &mut R, bits: u64) -> (BigUint, Certificate) {
+    let one = BigUint::one();
+    if bits < 33 {
+        loop {
+            let mut n = rng.gen_biguint(bits.max(2));
+            n.set_bit(bits.max(2) - 1, true);
+            if let Some(small) = n.to_u64().filter(|&n| is_small_prime(n)) {
+                return (n, Certificate::Small(small));
+            }
+        }
+    }
+
+    let (c, c_certificate) = certified_prime(rng, bits.div_ceil(2) + 1);
+    let two_c = &c << 1_u32;
+    let low = (&one << (bits - 1)).div_ceil(&two_c);
+    let high = (&one << bits) / &two_c;
+    loop {
+        let t = rng.gen_biguint_range(&low, &high);
+        let n = &t * &two_c + &one;
+        if has_small_factor(&n) || !miller_rabin_test(rng, &n, 1) {
+            continue;
+        }
+        if let Some(certificate) = pocklington(rng, &n, c_certificate.clone()) {
+            return (n, certificate);
+        }
+    }
+}
+
+// A `bits`-bit safe prime p = 2q + 1 with a certificate for p, which holds the
+// one for q: anyone can check both are prime without trusting the generator
+pub fn certified_safe_prime<R: CryptoRng + RngCore>(
+    rng: &mut R,
+    bits: u64,
+) -> Option<(BigUint, Certificate)> {
+    if bits < 3 {
+        return None;
+    }
+    loop {
+        let (q, q_certificate) = certified_prime(rng, bits - 1);
+        let p = (&q << 1_u32) + 1_u32;
+        if p.bits() != bits || has_small_factor(&p) || !miller_rabin_test(rng, &p, 1) {
+            continue;
+        }
+        if let Some(certificate) = pocklington(rng, &p, q_certificate) {
+            return Some((p, certificate));
+        }
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn test_shawe_taylor() {
+    for length in [2, 17, 32, 33, 100, 300] {
+        let mut seed = Seed {
+            value: BigUint::from(length),
+            len: 16,
+        };
+        let c = shawe_taylor_random_prime(length, &mut seed).unwrap();
+        assert_eq!(c.bits(), length);
+        assert!(is_prime(&c));
+    }
+
+    let e = BigUint::from(65537_u32);
+    let (p, q) = provable_primes(256, &e, b"seed").unwrap();
+    assert_eq!(
+        provable_primes(256, &e, b"seed"),
+        Some((p.clone(), q.clone()))
+    );
+    for x in [&p, &q] {
+        assert!(x >= &lower_bound(256) && x.bits() == 256 && is_prime(x));
+        assert!((x - 1_u32).gcd(&e).is_one());
+    }
+}
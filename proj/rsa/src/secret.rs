@@ -0,0 +1,101 @@
+// Secret integers of private keys: wiped when dropped, and redacted when printed
+use std::fmt::Debug;
+use std::ops::Deref;
+
+use num::Zero;
+use num_bigint::BigUint;
+use subtle::ConstantTimeEq;
+use zeroize::{Zeroize, ZeroizeOnDrop, Zeroizing};
+
+// A BigUint whose digits are overwritten on drop. It derefs to the BigUint for
+// reading only, so that no assignment can drop the old value without a wipe.
+#[derive(Clone, Default, Eq)]
+pub struct Secret(BigUint);
+
+impl Secret {
+    pub fn new(x: BigUint) -> Self {
+        Secret(x)
+    }
+}
+
+impl From<BigUint> for Secret {
+    fn from(x: BigUint) -> Self {
+        Secret(x)
+    }
+}
+
+impl Deref for Secret {
+    type Target = BigUint;
+
+    fn deref(&self) -> &BigUint {
+        &self.0
+    }
+}
+
+// `BigUint` has no access to its digit buffer, but `assign_from_slice` writes
+// over it in place: all ones first, which keeps the length so that the buffer
+// is not reallocated, then zero. Digits past the current length, left over
+// from larger values the buffer once held, are out of reach.
+impl Zeroize for Secret {
+    fn zeroize(&mut self) {
+        let ones = vec![u32::MAX; self.0.bits().div_ceil(32) as usize];
+        self.0.assign_from_slice(&ones);
+        std::hint::black_box(&self.0);
+        self.0.set_zero();
+    }
+}
+
+impl Drop for Secret {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
+impl ZeroizeOnDrop for Secret {}
+
+// Big-endian octets of x left-padded to `len`, wiped on drop
+fn padded_bytes(x: &BigUint, len: usize) -> Zeroizing<Vec<u8>> {
+    let bytes = Zeroizing::new(x.to_bytes_be());
+    let mut out = Zeroizing::new(Vec::with_capacity(len));
+    out.resize(len - bytes.len(), 0);
+    out.extend_from_slice(&bytes);
+    out
+}
+
+// Constant-time over the octets of the longer value, so only the lengths show
+fn ct_eq(a: &BigUint, b: &BigUint) -> bool {
+    let len = a.bits().max(b.bits()).div_ceil(8).max(1) as usize;
+    bool::from(padded_bytes(a, len).ct_eq(&padded_bytes(b, len)))
+}
+
+impl PartialEq for Secret {
+    fn eq(&self, other: &Secret) -> bool {
+        ct_eq(&self.0, &other.0)
+    }
+}
+
+impl PartialEq<BigUint> for Secret {
+    fn eq(&self, other: &BigUint) -> bool {
+        ct_eq(&self.0, other)
+    }
+}
+
+impl Debug for Secret {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("[REDACTED]")
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn test_secret() {
+    let mut secret = Secret::new(BigUint::from_bytes_be(&[0x5a; 64]));
+    assert_eq!(secret.bits(), 511);
+    assert_eq!(format!("{:?}", secret), "[REDACTED]");
+    assert!(secret == Secret::new(BigUint::from_bytes_be(&[0x5a; 64])));
+    assert!(secret != Secret::new(BigUint::from_bytes_be(&[0x5a; 63])));
+    assert!(secret != BigUint::zero());
+    assert!(Secret::default() == BigUint::zero());
+    secret.zeroize();
+    assert!(secret.is_zero());
+}
@@ -0,0 +1,341 @@
+// OpenSSH key formats: `ssh-rsa` public keys (RFC 4253 6.6) as used in
+// authorized_keys, and the openssh-key-v1 private key container (PROTOCOL.key)
+use aes::cipher::{KeyIvInit, StreamCipher};
+use base64::{engine::general_purpose, Engine};
+use num::Zero;
+use num_bigint::BigUint;
+use rand::{CryptoRng, RngCore};
+use sha2::{Digest, Sha256};
+use zeroize::Zeroizing;
+
+use crate::{
+    errors::{Error, Result},
+    pem,
+    plain_rsa::{PrivateKey, PublicKey},
+    secret::Secret,
+};
+
+pub const PRIVATE_KEY_LABEL: &str = "OPENSSH PRIVATE KEY";
+
+const KEY_TYPE: &[u8] = b"ssh-rsa";
+// Key types of authorized_keys lines that are not RSA keys
+const OTHER_KEY_TYPES: &[&str] = &[
+    "ssh-ed25519",
+    "ssh-dss",
+    "ecdsa-sha2-nistp256",
+    "ecdsa-sha2-nistp384",
+    "ecdsa-sha2-nistp521",
+    "sk-ssh-ed25519@openssh.com",
+    "sk-ecdsa-sha2-nistp256@openssh.com",
+];
+const AUTH_MAGIC: &[u8] = b"openssh-key-v1\0";
+const CIPHER: &[u8] = b"aes256-ctr";
+const KDF: &[u8] = b"bcrypt";
+const NONE: &[u8] = b"none";
+// ssh-keygen defaults for new keys
+const BCRYPT_ROUNDS: u32 = 16;
+const SALT_LEN: usize = 16;
+// ssh-keygen uses 16 rounds; each one costs milliseconds, so this is plenty
+const MAX_BCRYPT_ROUNDS: u32 = 1024;
+
+type Aes256Ctr = ctr::Ctr128BE<aes::Aes256>;
+
+// SSH wire encoding (RFC 4251 5)
+fn put_u32(out: &mut Vec<u8>, x: u32) {
+    out.extend_from_slice(&x.to_be_bytes());
+}
+
+fn put_string(out: &mut Vec<u8>, s: &[u8]) {
+    put_u32(out, s.len() as u32);
+    out.extend_from_slice(s);
+}
+
+// Two's complement, so a positive value gets a leading zero when its top bit is set.
+// The magnitude goes straight into `out`, as it may be a private integer.
+fn put_mpint(out: &mut Vec<u8>, x: &BigUint) {
+    if x.is_zero() {
+        return put_string(out, &[]);
+    }
+    let bytes = Zeroizing::new(x.to_bytes_be());
+    let pad = bytes[0] & 0x80 != 0;
+    put_u32(out, (bytes.len() + pad as usize) as u32);
+    if pad {
+        out.push(0);
+    }
+    out.extend_from_slice(&bytes);
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        if self.data.len() < len {
+            return Err(Error::MalformedEncoding);
+        }
+        let (head, tail) = self.data.split_at(len);
+        self.data = tail;
+        Ok(head)
+    }
+
+    fn read_u32(&mut self) -> Result<u32> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn read_string(&mut self) -> Result<&'a [u8]> {
+        let len = self.read_u32()? as usize;
+        self.take(len)
+    }
+
+    // Only non-negative, minimally encoded values
+    fn read_mpint(&mut self) -> Result<BigUint> {
+        let bytes = self.read_string()?;
+        match bytes {
+            [] => Ok(BigUint::zero()),
+            [b, ..] if b & 0x80 != 0 => Err(Error::MalformedEncoding),
+            [0, b, ..] if b & 0x80 == 0 => Err(Error::MalformedEncoding),
+            [0] => Err(Error::MalformedEncoding),
+            _ => Ok(BigUint::from_bytes_be(bytes)),
+        }
+    }
+
+    fn finish(self) -> Result<()> {
+        if self.data.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::MalformedEncoding)
+        }
+    }
+}
+
+// string "ssh-rsa" || mpint e || mpint n
+fn public_blob(n: &BigUint, e: &BigUint) -> Vec<u8> {
+    let mut blob = vec![];
+    put_string(&mut blob, KEY_TYPE);
+    put_mpint(&mut blob, e);
+    put_mpint(&mut blob, n);
+    blob
+}
+
+fn decode_public_blob(blob: &[u8]) -> Result<PublicKey> {
+    let mut reader = Reader { data: blob };
+    if reader.read_string()? != KEY_TYPE {
+        return Err(Error::WrongKeyType);
+    }
+    let e = reader.read_mpint()?;
+    let n = reader.read_mpint()?;
+    reader.finish()?;
+    if n.is_zero() || e.is_zero() {
+        return Err(Error::MalformedEncoding);
+    }
+    PublicKey::new(&n, &e)
+}
+
+// 32-octet AES key and 16-octet IV from bcrypt_pbkdf, which only fails on an
+// empty salt or zero rounds
+fn bcrypt_cipher(password: &[u8], salt: &[u8], rounds: u32) -> Result<Aes256Ctr> {
+    let mut key_iv = Zeroizing::new([0_u8; 48]);
+    bcrypt_pbkdf::bcrypt_pbkdf(password, salt, rounds, &mut key_iv[..])
+        .map_err(|_| Error::MalformedEncoding)?;
+    Ok(Aes256Ctr::new(key_iv[..32].into(), key_iv[32..].into()))
+}
+
+impl PublicKey {
+    // One authorized_keys line: `ssh-rsa <base64 blob> [comment]`
+    pub fn to_openssh(&self, comment: &str) -> String {
+        let blob = general_purpose::STANDARD.encode(public_blob(&self.n, &self.e));
+        if comment.is_empty() {
+            format!("ssh-rsa {}", blob)
+        } else {
+            format!("ssh-rsa {} {}", blob, comment)
+        }
+    }
+
+    // Accepts authorized_keys lines, including ones with leading options
+    pub fn from_openssh(line: &str) -> Result<Self> {
+        let mut fields = line
+            .split_whitespace()
+            .skip_while(|f| f.as_bytes() != KEY_TYPE && !OTHER_KEY_TYPES.contains(f));
+        if fields.next().ok_or(Error::MalformedEncoding)?.as_bytes() != KEY_TYPE {
+            return Err(Error::WrongKeyType);
+        }
+        let blob = fields
+            .next()
+            .and_then(|b| general_purpose::STANDARD.decode(b).ok())
+            .ok_or(Error::MalformedEncoding)?;
+        decode_public_blob(&blob)
+    }
+
+    // SHA256 fingerprint of the public key blob, e.g. `SHA256:XDM4iJg...`
+    pub fn ssh_fingerprint(&self) -> String {
+        let digest = Sha256::digest(public_blob(&self.n, &self.e));
+        format!("SHA256:{}", general_purpose::STANDARD_NO_PAD.encode(digest))
+    }
+
+    // The line printed by `ssh-keygen -l`
+    pub fn ssh_fingerprint_line(&self, comment: &str) -> String {
+        let comment = if comment.is_empty() {
+            "no comment"
+        } else {
+            comment
+        };
+        format!(
+            "{} {} {} (RSA)",
+            self.n.bits(),
+            self.ssh_fingerprint(),
+            comment
+        )
+    }
+}
+
+impl PrivateKey {
+    pub fn to_openssh_private(&self, comment: &str) -> Result<String> {
+        let mut rng = rand::thread_rng();
+        self.to_openssh_private_with_rng(&mut rng, comment, None)
+    }
+
+    // Encrypted with aes256-ctr under a bcrypt_pbkdf key, like `ssh-keygen -N`
+    pub fn to_openssh_private_encrypted(&self, comment: &str, password: &[u8]) -> Result<String> {
+        let mut rng = rand::thread_rng();
+        self.to_openssh_private_with_rng(&mut rng, comment, Some(password))
+    }
+
+    pub fn to_openssh_private_with_rng<R: RngCore + CryptoRng>(
+        &self,
+        rng: &mut R,
+        comment: &str,
+        password: Option<&[u8]>,
+    ) -> Result<String> {
+        // OpenSSH keys have room for p and q only
+        if !self.other_primes.is_empty() {
+            return Err(Error::WrongKeyType);
+        }
+        let mut salt = [0_u8; SALT_LEN];
+        if password.is_some() {
+            rng.fill_bytes(&mut salt);
+        }
+        // equal check integers tell a wrong password apart when decrypting
+        let mut check = [0_u8; 4];
+        rng.fill_bytes(&mut check);
+
+        // room for the six mpints, the comment and the padding, so the buffer never moves
+        let capacity = 64 + 6 * (self.size() + 5) + comment.len();
+        let mut private = Zeroizing::new(Vec::with_capacity(capacity));
+        private.extend_from_slice(&check);
+        private.extend_from_slice(&check);
+        put_string(&mut private, KEY_TYPE);
+        for x in [&self.n, &self.e, &self.d, &self.qinv, &self.p, &self.q] {
+            put_mpint(&mut private, x);
+        }
+        put_string(&mut private, comment.as_bytes());
+        let block_size = if password.is_some() { 16 } else { 8 };
+        let mut pad: u8 = 1;
+        while private.len() % block_size != 0 {
+            private.push(pad);
+            pad += 1;
+        }
+
+        let mut out = AUTH_MAGIC.to_vec();
+        match password {
+            Some(password) => {
+                let mut cipher = bcrypt_cipher(password, &salt, BCRYPT_ROUNDS)
+                    .map_err(|_| Error::EncodingError)?;
+                cipher.apply_keystream(&mut private);
+
+                let mut kdf_options = vec![];
+                put_string(&mut kdf_options, &salt);
+                put_u32(&mut kdf_options, BCRYPT_ROUNDS);
+                put_string(&mut out, CIPHER);
+                put_string(&mut out, KDF);
+                put_string(&mut out, &kdf_options);
+            }
+            None => {
+                put_string(&mut out, NONE);
+                put_string(&mut out, NONE);
+                put_string(&mut out, &[]);
+            }
+        }
+        put_u32(&mut out, 1);
+        put_string(&mut out, &public_blob(&self.n, &self.e));
+        put_string(&mut out, &private);
+
+        Ok(pem::encode_wrapped(PRIVATE_KEY_LABEL, &out, 70))
+    }
+
+    // `password` is only used, and then required, for encrypted keys
+    pub fn from_openssh_private(text: &str, password: Option<&[u8]>) -> Result<Self> {
+        let bytes = pem::decode_label(text, PRIVATE_KEY_LABEL)?;
+        let mut reader = Reader {
+            data: bytes
+                .strip_prefix(AUTH_MAGIC)
+                .ok_or(Error::MalformedEncoding)?,
+        };
+
+        let cipher_name = reader.read_string()?;
+        let kdf_name = reader.read_string()?;
+        let kdf_options = reader.read_string()?;
+        if reader.read_u32()? != 1 {
+            return Err(Error::MalformedEncoding);
+        }
+        let public = decode_public_blob(reader.read_string()?)?;
+        let mut private = Zeroizing::new(reader.read_string()?.to_vec());
+        reader.finish()?;
+
+        let encrypted = match (cipher_name, kdf_name) {
+            (NONE, NONE) if kdf_options.is_empty() => false,
+            (CIPHER, KDF) => {
+                let mut options = Reader { data: kdf_options };
+                let salt = options.read_string()?;
+                let rounds = options.read_u32()?;
+                options.finish()?;
+                if salt.is_empty() || !(1..=MAX_BCRYPT_ROUNDS).contains(&rounds) {
+                    return Err(Error::MalformedEncoding);
+                }
+                if private.len() % 16 != 0 {
+                    return Err(Error::MalformedEncoding);
+                }
+
+                let password = password.ok_or(Error::PasswordRequired)?;
+                let mut cipher = bcrypt_cipher(password, salt, rounds)?;
+                cipher.apply_keystream(&mut private);
+                true
+            }
+            _ => return Err(Error::MalformedEncoding),
+        };
+
+        let mut reader = Reader { data: &private };
+        if reader.read_u32()? != reader.read_u32()? {
+            return Err(if encrypted {
+                Error::WrongPassword
+            } else {
+                Error::MalformedEncoding
+            });
+        }
+        if reader.read_string()? != KEY_TYPE {
+            return Err(Error::WrongKeyType);
+        }
+        let n = reader.read_mpint()?;
+        let e = reader.read_mpint()?;
+        let d = Secret::new(reader.read_mpint()?);
+        let qinv = Secret::new(reader.read_mpint()?);
+        let p = Secret::new(reader.read_mpint()?);
+        let q = Secret::new(reader.read_mpint()?);
+        reader.read_string()?;
+        for (i, &pad) in reader.data.iter().enumerate() {
+            if pad as usize != i + 1 {
+                return Err(Error::MalformedEncoding);
+            }
+        }
+
+        if public.n != n || public.e != e {
+            return Err(Error::InvalidKeyPair("the public key does not match"));
+        }
+        let sk = PrivateKey::from_components(&n, &e, &d, &p, &q)?;
+        if sk.qinv != qinv {
+            return Err(Error::InvalidKeyPair("inconsistent CRT parameters"));
+        }
+        Ok(sk)
+    }
+}
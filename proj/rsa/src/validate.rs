@@ -0,0 +1,98 @@
+// Key pair validation, after the checks of NIST SP 800-56B 6.4.1 and
+// FIPS 186-5 5.4 (pairwise consistency)
+use num::{Integer, One, Zero};
+use num_bigint::BigUint;
+use sha2::Sha256;
+
+use crate::{
+    algorithms::is_prime,
+    errors::{Error, Result},
+    plain_rsa::{PrivateKey, PublicKey},
+};
+
+// Smallest modulus accepted for new keys (NIST SP 800-131A)
+pub const MIN_MODULUS_BITS: u64 = 2048;
+
+const PAIRWISE_TEST_MESSAGE: &[u8] = b"pairwise consistency test";
+
+impl PrivateKey {
+    // Checks that all components belong to one RSA key of at least
+    // MIN_MODULUS_BITS bits, then signs and verifies a test message
+    pub fn validate(&self) -> Result<()> {
+        self.check_components()?;
+        self.pairwise_test(&PublicKey::new(&self.n, &self.e)?)
+    }
+
+    // Same as `validate`, and `pk` has to be the public half of this key
+    pub fn validate_pair(&self, pk: &PublicKey) -> Result<()> {
+        if pk.n != self.n || pk.e != self.e {
+            return Err(Error::InvalidKeyPair("the public key does not match"));
+        }
+        self.check_components()?;
+        self.pairwise_test(pk)
+    }
+
+    fn check_components(&self) -> Result<()> {
+        let one = BigUint::one();
+
+        if self.n.bits() < MIN_MODULUS_BITS {
+            return Err(Error::InvalidKeyPair("the modulus is too short"));
+        }
+        if self.e <= one || self.e.is_even() || self.e >= self.n {
+            return Err(Error::InvalidKeyPair("e is not an odd integer in (1, n)"));
+        }
+        let primes: Vec<&BigUint> = self.primes().collect();
+        if primes.iter().copied().product::<BigUint>() != self.n {
+            return Err(Error::InvalidKeyPair(if primes.len() == 2 {
+                "p * q is not n"
+            } else {
+                "the product of the primes is not n"
+            }));
+        }
+        if self.p == self.q {
+            return Err(Error::InvalidKeyPair("p and q are equal"));
+        }
+        if (1..primes.len()).any(|i| primes[..i].contains(&primes[i])) {
+            return Err(Error::InvalidKeyPair("the primes are not distinct"));
+        }
+        if !is_prime(&self.p) {
+            return Err(Error::InvalidKeyPair("p is not prime"));
+        }
+        if !is_prime(&self.q) {
+            return Err(Error::InvalidKeyPair("q is not prime"));
+        }
+        if !primes[2..].iter().all(|r| is_prime(r)) {
+            return Err(Error::InvalidKeyPair("a further prime is not prime"));
+        }
+
+        // d is only required to be an inverse modulo lambda(n), the lcm of the
+        // r_i - 1, so both the phi(n) and the lambda(n) conventions pass
+        let lambda_n = primes
+            .iter()
+            .fold(one.clone(), |lambda, &r| lambda.lcm(&(r - &one)));
+        if self.d.is_zero() || *self.d >= self.n || (&self.e * &*self.d) % lambda_n != one {
+            return Err(Error::InvalidKeyPair("e * d is not 1 mod lambda(n)"));
+        }
+
+        let (d, p, q) = (&*self.d, &*self.p, &*self.q);
+        let mut r_product = p * q;
+        let mut consistent =
+            self.dp == d % (p - &one) && self.dq == d % (q - &one) && (&*self.qinv * q) % p == one;
+        for info in &self.other_primes {
+            let r = &*info.prime;
+            consistent &=
+                info.exponent == d % (r - &one) && (&*info.coefficient * &r_product) % r == one;
+            r_product *= r;
+        }
+        if !consistent {
+            return Err(Error::InvalidKeyPair("inconsistent CRT parameters"));
+        }
+        Ok(())
+    }
+
+    fn pairwise_test(&self, pk: &PublicKey) -> Result<()> {
+        self.sign_pkcs1v15::<Sha256>(PAIRWISE_TEST_MESSAGE)
+            .and_then(|signature| pk.verify_pkcs1v15::<Sha256>(PAIRWISE_TEST_MESSAGE, &signature))
+            .map_err(|_| Error::InvalidKeyPair("pairwise consistency test failed"))
+    }
+}
@@ -0,0 +1,76 @@
+// A test binary of its own: the global allocator below looks into every block
+// freed while it is armed, which the other tests must not run concurrently with
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    sync::atomic::{AtomicBool, AtomicU64, Ordering},
+};
+
+use my_rsa::*;
+
+// Frees blocks like `System`, after checking them for the 16 bytes of `NEEDLE`
+struct Scanner;
+
+static ARMED: AtomicBool = AtomicBool::new(false);
+static FOUND: AtomicBool = AtomicBool::new(false);
+static NEEDLE: [AtomicU64; 2] = [AtomicU64::new(0), AtomicU64::new(0)];
+
+unsafe impl GlobalAlloc for Scanner {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        if ARMED.load(Ordering::SeqCst) {
+            let mut needle = [0_u8; 16];
+            needle[..8].copy_from_slice(&NEEDLE[0].load(Ordering::SeqCst).to_ne_bytes());
+            needle[8..].copy_from_slice(&NEEDLE[1].load(Ordering::SeqCst).to_ne_bytes());
+            let block = std::slice::from_raw_parts(ptr, layout.size());
+            if block.windows(needle.len()).any(|window| window == needle) {
+                FOUND.store(true, Ordering::SeqCst);
+            }
+        }
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: Scanner = Scanner;
+
+// Whether the lowest two digits of `secret` are still in a block freed by `f`
+fn leaks_on_drop(secret: &num_bigint::BigUint, f: impl FnOnce()) -> bool {
+    let mut digits = secret.iter_u64_digits();
+    for atomic in &NEEDLE {
+        atomic.store(digits.next().unwrap(), Ordering::SeqCst);
+    }
+    FOUND.store(false, Ordering::SeqCst);
+    ARMED.store(true, Ordering::SeqCst);
+    f();
+    ARMED.store(false, Ordering::SeqCst);
+    FOUND.load(Ordering::SeqCst)
+}
+
+#[test]
+fn test_zeroize_on_drop() {
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha20Rng;
+
+    let gen = generator::Generator::builder()
+        .bits(1024)
+        .build_with_rng(&mut ChaCha20Rng::seed_from_u64(25))
+        .unwrap();
+    let d = (*gen.d).clone();
+
+    // the scan does see a plain copy of d
+    let copy = d.clone();
+    assert!(leaks_on_drop(&d, || drop(copy)));
+
+    let secret = secret::Secret::new(d.clone());
+    assert!(!leaks_on_drop(&d, || drop(secret)));
+
+    let sk = plain_rsa::PrivateKey::try_from(&gen).unwrap();
+    let p = (*sk.p).clone();
+    assert!(!leaks_on_drop(&d, || drop(sk.clone())));
+    assert!(!leaks_on_drop(&p, || drop(sk)));
+
+    assert!(!leaks_on_drop(&d, || drop(gen)));
+}
@@ -1,3 +1,13 @@
+use digest::{
+    block_buffer::Eager,
+    core_api::{
+        AlgorithmName, Block, BlockSizeUser, Buffer, BufferKindUser, CoreWrapper, FixedOutputCore,
+        OutputSizeUser, UpdateCore,
+    },
+    typenum::{U32, U64},
+    HashMarker, Output, Reset,
+};
+
 const IV: [u32; 8] = [
     0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19,
 ];
@@ -33,6 +43,7 @@ fn low_sigma1(x: u32) -> u32 {
     rightrotate32(x, 17) ^ rightrotate32(x, 19) ^ (x >> 10)
 }
 
+#[allow(clippy::same_item_push)]
 fn padding(length: u64) -> Vec<u8> {
     let mut padding_bytes = vec![0x80];
     let remainder_bytes = (length + 8) % 64;
@@ -114,7 +125,7 @@ fn get_hash(state: &[u32; 8]) -> [u8; 32] {
     hash
 }
 
-fn sha256(message: &[u8]) -> [u8; 32] {
+pub fn sha256(message: &[u8]) -> [u8; 32] {
     let mut padded_message = message.to_vec();
     padded_message.extend_from_slice(&padding(message.len() as u64));
     assert_eq!(0, padded_message.len() % 64);
@@ -125,7 +136,67 @@ fn sha256(message: &[u8]) -> [u8; 32] {
     get_hash(&state)
 }
 
-fn main() {}
+// Streaming core, so that the hash can be used through the `digest::Digest` trait
+#[derive(Clone)]
+pub struct Sha256Core {
+    state: [u32; 8],
+    block_len: u64,
+}
+
+pub type Sha256 = CoreWrapper<Sha256Core>;
+
+impl Default for Sha256Core {
+    fn default() -> Self {
+        Sha256Core {
+            state: IV,
+            block_len: 0,
+        }
+    }
+}
+
+impl HashMarker for Sha256Core {}
+
+impl BlockSizeUser for Sha256Core {
+    type BlockSize = U64;
+}
+
+impl BufferKindUser for Sha256Core {
+    type BufferKind = Eager;
+}
+
+impl OutputSizeUser for Sha256Core {
+    type OutputSize = U32;
+}
+
+impl UpdateCore for Sha256Core {
+    fn update_blocks(&mut self, blocks: &[Block<Self>]) {
+        for block in blocks {
+            self.block_len += 1;
+            self.state = compress(&self.state, block.as_ref());
+        }
+    }
+}
+
+impl FixedOutputCore for Sha256Core {
+    fn finalize_fixed_core(&mut self, buffer: &mut Buffer<Self>, out: &mut Output<Self>) {
+        let bit_len = 8 * (64 * self.block_len + buffer.get_pos() as u64);
+        let mut state = self.state;
+        buffer.len64_padding_be(bit_len, |block| state = compress(&state, block.as_ref()));
+        out.copy_from_slice(&get_hash(&state));
+    }
+}
+
+impl Reset for Sha256Core {
+    fn reset(&mut self) {
+        *self = Default::default();
+    }
+}
+
+impl AlgorithmName for Sha256Core {
+    fn write_alg_name(f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str("Sha256")
+    }
+}
 
 #[test]
 fn test_sha256() {
@@ -141,3 +212,17 @@ fn test_sha256() {
         assert_eq!(my_hash[..], expected[..]);
     }
 }
+
+#[test]
+fn test_sha256_digest() {
+    use digest::Digest;
+    for i in 0..200 {
+        let input = vec![i as u8; i];
+        let mut hasher = Sha256::new();
+        // feed in uneven chunks to exercise the block buffer
+        for chunk in input.chunks(7) {
+            hasher.update(chunk);
+        }
+        assert_eq!(hasher.finalize()[..], sha256(&input)[..]);
+    }
+}